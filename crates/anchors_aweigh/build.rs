@@ -0,0 +1,7 @@
+fn main() {
+    // grammars compiled while running are built for the same
+    // target as this crate, which is only known to build scripts
+    let target = std::env::var("TARGET").expect("cargo sets TARGET");
+    println!("cargo:rustc-env=ANCHORS_AWEIGH_TARGET={target}");
+    println!("cargo:rerun-if-changed=build.rs");
+}
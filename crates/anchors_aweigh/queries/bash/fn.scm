@@ -0,0 +1,6 @@
+(
+  (comment)*
+  .
+  (function_definition name: (word) @name)
+  (#eq? @name "{{name}}")
+) @match
@@ -0,0 +1,6 @@
+(
+  (comment)*
+  .
+  (function_definition declarator: (function_declarator declarator: (identifier) @name))
+  (#eq? @name "{{name}}")
+) @match
@@ -0,0 +1,6 @@
+(
+  (comment)*
+  .
+  (class_specifier name: (type_identifier) @name body: (_))
+  (#eq? @name "{{name}}")
+) @match
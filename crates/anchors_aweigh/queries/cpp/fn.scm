@@ -0,0 +1,6 @@
+(
+  (comment)*
+  .
+  (function_definition declarator: (function_declarator declarator: [(identifier) (field_identifier) (qualified_identifier)] @name))
+  (#eq? @name "{{name}}")
+) @match
@@ -0,0 +1,6 @@
+(
+  (comment)*
+  .
+  (struct_specifier name: (type_identifier) @name body: (_))
+  (#eq? @name "{{name}}")
+) @match
@@ -0,0 +1 @@
+(rule_set (selectors) @name (#eq? @name "{{name}}")) @match
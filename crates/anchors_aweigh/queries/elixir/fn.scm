@@ -0,0 +1,14 @@
+(
+  [(comment) (unary_operator)]*
+  .
+  (call
+    target: (identifier) @kind
+    (arguments
+      [
+        (call target: (identifier) @name)
+        (identifier) @name
+        (binary_operator left: (call target: (identifier) @name))
+      ]))
+  (#any-of? @kind "def" "defp" "defmacro" "defmacrop")
+  (#eq? @name "{{name}}")
+) @match
@@ -0,0 +1,7 @@
+(
+  [(comment) (unary_operator)]*
+  .
+  (call target: (identifier) @kind (arguments (alias) @name))
+  (#eq? @kind "defmodule")
+  (#eq? @name "{{name}}")
+) @match
@@ -0,0 +1,6 @@
+(
+  (comment)*
+  .
+  (function_declaration name: (identifier) @name)
+  (#eq? @name "{{name}}")
+) @match
@@ -0,0 +1,6 @@
+(
+  (comment)*
+  .
+  (method_declaration name: (field_identifier) @name)
+  (#eq? @name "{{name}}")
+) @match
@@ -0,0 +1,6 @@
+(
+  (comment)*
+  .
+  (type_declaration (type_spec name: (type_identifier) @name))
+  (#eq? @name "{{name}}")
+) @match
@@ -0,0 +1,7 @@
+(element
+  (start_tag
+    (attribute
+      (attribute_name) @attr
+      (quoted_attribute_value (attribute_value) @id)))
+  (#eq? @attr "id")
+  (#eq? @id "{{id}}")) @match
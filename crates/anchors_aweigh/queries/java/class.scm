@@ -0,0 +1,6 @@
+(
+  [(line_comment) (block_comment)]*
+  .
+  (class_declaration name: (identifier) @name)
+  (#eq? @name "{{name}}")
+) @match
@@ -0,0 +1,6 @@
+(
+  [(line_comment) (block_comment)]*
+  .
+  (enum_declaration name: (identifier) @name)
+  (#eq? @name "{{name}}")
+) @match
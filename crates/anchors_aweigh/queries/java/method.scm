@@ -0,0 +1,6 @@
+(
+  [(line_comment) (block_comment)]*
+  .
+  [(method_declaration name: (identifier) @name) (constructor_declaration name: (identifier) @name)]
+  (#eq? @name "{{name}}")
+) @match
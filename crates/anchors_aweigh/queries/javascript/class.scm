@@ -0,0 +1,19 @@
+(
+  [
+    (program
+      (comment)* @match
+      .
+      [
+        (class_declaration name: (identifier) @name)
+        (export_statement declaration: (class_declaration name: (identifier) @name))
+      ] @match)
+    (statement_block
+      (comment)* @match
+      .
+      [
+        (class_declaration name: (identifier) @name)
+        (export_statement declaration: (class_declaration name: (identifier) @name))
+      ] @match)
+  ]
+  (#eq? @name "{{name}}")
+)
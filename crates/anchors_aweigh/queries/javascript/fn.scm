@@ -0,0 +1,23 @@
+(
+  [
+    (program
+      (comment)* @match
+      .
+      [
+        (function_declaration name: (identifier) @name)
+        (generator_function_declaration name: (identifier) @name)
+        (export_statement declaration: (function_declaration name: (identifier) @name))
+        (export_statement declaration: (generator_function_declaration name: (identifier) @name))
+      ] @match)
+    (statement_block
+      (comment)* @match
+      .
+      [
+        (function_declaration name: (identifier) @name)
+        (generator_function_declaration name: (identifier) @name)
+        (export_statement declaration: (function_declaration name: (identifier) @name))
+        (export_statement declaration: (generator_function_declaration name: (identifier) @name))
+      ] @match)
+  ]
+  (#eq? @name "{{name}}")
+)
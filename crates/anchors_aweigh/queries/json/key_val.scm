@@ -0,0 +1 @@
+(pair key: (string (string_content) @key) (#eq? @key "{{key}}")) @match
@@ -0,0 +1 @@
+(section (atx_heading (inline) @name) (#eq? @name "{{name}}")) @match
@@ -0,0 +1,19 @@
+(
+  [
+    (module
+      (comment)* @match
+      .
+      [
+        (class_definition name: (identifier) @name)
+        (decorated_definition definition: (class_definition name: (identifier) @name))
+      ] @match)
+    (block
+      (comment)* @match
+      .
+      [
+        (class_definition name: (identifier) @name)
+        (decorated_definition definition: (class_definition name: (identifier) @name))
+      ] @match)
+  ]
+  (#eq? @name "{{name}}")
+)
@@ -0,0 +1,19 @@
+(
+  [
+    (module
+      (comment)* @match
+      .
+      [
+        (function_definition name: (identifier) @name)
+        (decorated_definition definition: (function_definition name: (identifier) @name))
+      ] @match)
+    (block
+      (comment)* @match
+      .
+      [
+        (function_definition name: (identifier) @name)
+        (decorated_definition definition: (function_definition name: (identifier) @name))
+      ] @match)
+  ]
+  (#eq? @name "{{name}}")
+)
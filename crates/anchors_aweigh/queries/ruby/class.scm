@@ -0,0 +1,6 @@
+(
+  (comment)*
+  .
+  (class name: (constant) @name)
+  (#eq? @name "{{name}}")
+) @match
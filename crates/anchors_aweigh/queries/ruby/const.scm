@@ -0,0 +1,6 @@
+(
+  (comment)*
+  .
+  (assignment left: (constant) @name)
+  (#eq? @name "{{name}}")
+) @match
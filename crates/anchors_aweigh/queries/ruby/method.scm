@@ -0,0 +1,6 @@
+(
+  (comment)*
+  .
+  [(method name: (_) @name) (singleton_method name: (_) @name)]
+  (#eq? @name "{{name}}")
+) @match
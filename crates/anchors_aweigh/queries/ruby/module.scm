@@ -0,0 +1,6 @@
+(
+  (comment)*
+  .
+  (module name: (constant) @name)
+  (#eq? @name "{{name}}")
+) @match
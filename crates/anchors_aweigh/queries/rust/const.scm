@@ -0,0 +1,6 @@
+(
+  [(line_comment) (block_comment) (attribute_item)]*
+  .
+  [(const_item name: (identifier) @name) (static_item name: (identifier) @name)]
+  (#eq? @name "{{name}}")
+) @match
@@ -0,0 +1,6 @@
+(
+  [(line_comment) (block_comment) (attribute_item)]*
+  .
+  (impl_item type: [(type_identifier) @name (generic_type type: (type_identifier) @name)])
+  (#eq? @name "{{name}}")
+) @match
@@ -0,0 +1,6 @@
+(
+  [(line_comment) (block_comment) (attribute_item)]*
+  .
+  (macro_definition name: (identifier) @name)
+  (#eq? @name "{{name}}")
+) @match
@@ -0,0 +1,6 @@
+(
+  [(line_comment) (block_comment) (attribute_item)]*
+  .
+  (type_item name: (type_identifier) @name)
+  (#eq? @name "{{name}}")
+) @match
@@ -0,0 +1,3 @@
+(create_table
+  (object_reference name: (identifier) @name)
+  (#eq? @name "{{name}}")) @match
@@ -0,0 +1 @@
+(pair (bare_key) @key (#eq? @key "{{key}}")) @match
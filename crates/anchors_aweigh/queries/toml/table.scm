@@ -0,0 +1,6 @@
+(
+  (comment)*
+  .
+  [(table (bare_key) @name) (table_array_element (bare_key) @name)]
+  (#eq? @name "{{name}}")
+) @match
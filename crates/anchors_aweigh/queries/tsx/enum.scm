@@ -0,0 +1,19 @@
+(
+  [
+    (program
+      (comment)* @match
+      .
+      [
+        (enum_declaration name: (identifier) @name)
+        (export_statement declaration: (enum_declaration name: (identifier) @name))
+      ] @match)
+    (statement_block
+      (comment)* @match
+      .
+      [
+        (enum_declaration name: (identifier) @name)
+        (export_statement declaration: (enum_declaration name: (identifier) @name))
+      ] @match)
+  ]
+  (#eq? @name "{{name}}")
+)
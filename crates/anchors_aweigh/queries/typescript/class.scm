@@ -0,0 +1,23 @@
+(
+  [
+    (program
+      (comment)* @match
+      .
+      [
+        (class_declaration name: (type_identifier) @name)
+        (abstract_class_declaration name: (type_identifier) @name)
+        (export_statement declaration: (class_declaration name: (type_identifier) @name))
+        (export_statement declaration: (abstract_class_declaration name: (type_identifier) @name))
+      ] @match)
+    (statement_block
+      (comment)* @match
+      .
+      [
+        (class_declaration name: (type_identifier) @name)
+        (abstract_class_declaration name: (type_identifier) @name)
+        (export_statement declaration: (class_declaration name: (type_identifier) @name))
+        (export_statement declaration: (abstract_class_declaration name: (type_identifier) @name))
+      ] @match)
+  ]
+  (#eq? @name "{{name}}")
+)
@@ -0,0 +1,19 @@
+(
+  [
+    (program
+      (comment)* @match
+      .
+      [
+        (interface_declaration name: (type_identifier) @name)
+        (export_statement declaration: (interface_declaration name: (type_identifier) @name))
+      ] @match)
+    (statement_block
+      (comment)* @match
+      .
+      [
+        (interface_declaration name: (type_identifier) @name)
+        (export_statement declaration: (interface_declaration name: (type_identifier) @name))
+      ] @match)
+  ]
+  (#eq? @name "{{name}}")
+)
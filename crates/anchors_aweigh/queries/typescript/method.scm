@@ -0,0 +1,6 @@
+(
+  (comment)*
+  .
+  (method_definition name: (property_identifier) @name)
+  (#eq? @name "{{name}}")
+) @match
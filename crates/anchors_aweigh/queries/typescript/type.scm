@@ -0,0 +1,19 @@
+(
+  [
+    (program
+      (comment)* @match
+      .
+      [
+        (type_alias_declaration name: (type_identifier) @name)
+        (export_statement declaration: (type_alias_declaration name: (type_identifier) @name))
+      ] @match)
+    (statement_block
+      (comment)* @match
+      .
+      [
+        (type_alias_declaration name: (type_identifier) @name)
+        (export_statement declaration: (type_alias_declaration name: (type_identifier) @name))
+      ] @match)
+  ]
+  (#eq? @name "{{name}}")
+)
@@ -0,0 +1 @@
+(block_mapping_pair key: (flow_node) @key (#eq? @key "{{key}}")) @match
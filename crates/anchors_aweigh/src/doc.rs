@@ -5,21 +5,27 @@
 //!
 
 mod anchor;
+mod diagnostic;
 mod file;
 mod list;
 mod token;
 
 pub use anchor::{
-    Anchor, Decoration, Link, NamedAnchor, ParseQueryAnchorError, QueryAnchor, Strategy,
+    Anchor, BindingValue, Bindings, Decoration, Extract, Link, MatchSelector, NamedAnchor,
+    ParseQueryAnchorError, QueryAnchor, Selection, Stage, Strategy,
 };
+pub use diagnostic::Diagnostic;
 pub use file::DocFile;
 pub use list::DocList;
-pub use token::Token;
+pub use token::{ParseOptions, Token};
 
 /// All of the variants of errors that can be encountered
 /// when working with the logical concepts in [crate::source]
 #[derive(Debug, thiserror::Error)]
 pub enum DocError {
+    #[error(transparent)]
+    Located(#[from] Diagnostic),
+
     #[error(transparent)]
     ParseToken(#[from] token::ParseError),
 
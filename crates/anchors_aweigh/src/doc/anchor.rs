@@ -14,12 +14,14 @@ mod parser;
 mod query_anchor;
 mod strategy;
 
-pub use decoration::Decoration;
+pub use decoration::{Decoration, Stage};
 pub use link::Link;
 pub use named_anchor::NamedAnchor;
 pub use parser::ParseError;
-pub use query_anchor::{ParseQueryAnchorError, QueryAnchor};
-pub use strategy::Strategy;
+pub use query_anchor::{
+    BindingValue, Bindings, Extract, MatchSelector, ParseQueryAnchorError, QueryAnchor,
+};
+pub use strategy::{Selection, Strategy};
 
 use super::DocError;
 
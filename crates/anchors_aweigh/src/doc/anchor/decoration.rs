@@ -1,16 +1,58 @@
 use serde::{Deserialize, Serialize};
 
+/// Ordered stages the linked contents pass through, each working
+/// on what the one before it produced, such as `(<|trim|codeblock)`.
+/// Without any stages the source is left alone.
 #[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
-pub enum Decoration {
-    /// Leave the source alone
-    #[default]
-    None,
+pub struct Decoration(pub Vec<Stage>);
 
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Stage {
     /// Remove repeating leading whitespace from the
     /// start of all source lines,  great for nested
     /// functions you want too bring focus to
     LeftShift,
 
+    /// Remove blank lines from the start and end
+    Trim,
+
+    /// Wrap in a fenced code block tagged with the language of
+    /// the source followed by any extra attributes, such as
+    /// `(fence,ignore,hl_lines=3-5)`
+    Fence(Vec<String>),
+
+    /// Replace the bodies of functions and methods with a
+    /// placeholder, keeping their signatures, doc comments
+    /// and attributes, handy for an overview of an API.
+    /// Bodies are found in the tree of the whole source, so
+    /// this starts over from the selection and may only come
+    /// first, anywhere else or along with [Stage::Hidden]
+    /// being an error when the anchor is parsed.
+    Elide,
+
     /// results are processed via a handlesbar template
     Template(String),
+
+    /// The whole file is included with every line outside
+    /// of the selection hidden by prefixing it with `# `,
+    /// as mdbook's `{{#rustdoc_include ...}}` does.  This
+    /// starts over from the file so it may only come first,
+    /// anywhere else being an error when the anchor is parsed.
+    Hidden,
+}
+
+impl Decoration {
+    pub fn stages(&self) -> &[Stage] {
+        &self.0
+    }
+
+    pub fn is_none(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl From<Stage> for Decoration {
+    fn from(stage: Stage) -> Self {
+        Self(vec![stage])
+    }
 }
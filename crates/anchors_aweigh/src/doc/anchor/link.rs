@@ -6,4 +6,14 @@ use std::path::PathBuf;
 pub struct Link {
     pub path: PathBuf,
     pub strategy: Strategy,
+
+    /// name of the language the file is parsed as,
+    /// given by a `[lang=name]` after the path
+    #[serde(default)]
+    pub language: Option<String>,
+
+    /// git revision the file is read from,
+    /// given by an `@revision` after the path
+    #[serde(default)]
+    pub revision: Option<String>,
 }
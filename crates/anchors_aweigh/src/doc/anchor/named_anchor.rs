@@ -1,7 +1,7 @@
-use nom::bytes::complete::{tag, take_until};
+use nom::bytes::complete::{tag, take_until, take_while1};
 use nom::character::complete::multispace1;
 use nom::combinator::{recognize, rest};
-use nom::sequence::delimited;
+use nom::sequence::{delimited, preceded};
 use nom::{IResult, Parser};
 use serde::{Deserialize, Serialize};
 
@@ -16,6 +16,10 @@ impl NamedAnchor {
         Self(value.into())
     }
 
+    pub fn name(&self) -> &str {
+        &self.0
+    }
+
     pub fn missing_start(&self, line: &str) -> bool {
         find("ANCHOR:", self.0.as_ref(), line).is_err()
     }
@@ -23,6 +27,26 @@ impl NamedAnchor {
     pub fn missing_end(&self, line: &str) -> bool {
         find("ANCHOR_END:", self.0.as_ref(), line).is_err()
     }
+
+    /// if the line marks the start or end of any named anchor
+    pub fn is_marker(line: &str) -> bool {
+        ["ANCHOR:", "ANCHOR_END:"]
+            .iter()
+            .any(|marker| marker_name(marker, line).is_ok())
+    }
+
+    /// the text without any of the lines marking named anchors
+    pub fn strip_markers(text: &str) -> String {
+        let mut kept: String = text
+            .split_inclusive('\n')
+            .filter(|line| !Self::is_marker(line))
+            .collect();
+        // the last line kept may not have been the last line
+        if !text.ends_with('\n') && kept.ends_with('\n') {
+            kept.pop();
+        }
+        kept
+    }
 }
 
 fn find<'a>(anchor: &str, name: &str, input: &'a str) -> IResult<&'a str, &'a str> {
@@ -31,6 +55,11 @@ fn find<'a>(anchor: &str, name: &str, input: &'a str) -> IResult<&'a str, &'a st
     delimited(take_until(anchor), token, rest).parse(input)
 }
 
+fn marker_name<'a>(marker: &str, input: &'a str) -> IResult<&'a str, &'a str> {
+    let name = take_while1(|c: char| c.is_alphanumeric() || c == '_' || c == '-');
+    preceded((take_until(marker), tag(marker), multispace1), name).parse(input)
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -42,4 +71,14 @@ mod test {
         assert_eq!(rem, "");
         assert_eq!(token, "ANCHOR: test");
     }
+
+    #[test]
+    fn markers_are_stripped() {
+        let text = "// ANCHOR: outer\nfn a() {}\n  # ANCHOR_END: inner-1\nlet s = \"ANCHOR:\";\n// ANCHOR_END: outer";
+        assert_eq!(
+            NamedAnchor::strip_markers(text),
+            "fn a() {}\nlet s = \"ANCHOR:\";"
+        );
+        assert_eq!(NamedAnchor::strip_markers("no markers"), "no markers");
+    }
 }
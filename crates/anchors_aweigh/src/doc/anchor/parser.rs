@@ -1,57 +1,196 @@
-use super::{Anchor, Decoration, Link, NamedAnchor, Strategy, query_anchor::query_anchor};
+use super::{
+    Anchor, Decoration, Link, NamedAnchor, QueryAnchor, Stage, Strategy, query_anchor::query_anchor,
+};
 use nom::branch::alt;
 use nom::bytes::complete::{is_not, tag};
 use nom::character::complete::{alphanumeric1, digit1, multispace0};
 use nom::combinator::{eof, map_res, opt, recognize, value};
-use nom::error::Error;
-use nom::multi::many1_count;
+use nom::multi::{many0, many1_count, separated_list1};
 use nom::sequence::{delimited, pair, preceded, separated_pair, terminated};
 use nom::{Finish, IResult, Parser};
 use std::path::PathBuf;
 
 #[derive(Debug, thiserror::Error)]
-#[error("{0:?}")]
-pub struct ParseError(#[from] Error<String>);
+#[error("{message} at offset {offset}")]
+pub struct ParseError {
+    /// byte offset into the anchor where parsing failed
+    pub offset: usize,
+
+    /// human explanation of what went wrong
+    pub message: String,
+}
+
+impl ParseError {
+    fn at(input: &str, rem: &str, message: &str) -> Self {
+        Self {
+            offset: input.len() - rem.len(),
+            message: message.to_owned(),
+        }
+    }
+}
 
 pub(super) fn parse(source: &str) -> Result<Anchor, ParseError> {
+    let input = source.trim_end();
+
     let (rem, decoration) = preceded(multispace0, decoration_part)
-        .parse(source)
+        .parse(input)
         .finish()
-        .map_err(|err| ParseError(err.into()))?;
+        .map_err(|err| ParseError::at(input, err.input, "unknown decoration"))?;
+
+    if rem.starts_with('(') {
+        return Err(ParseError::at(
+            input,
+            rem,
+            "unknown decoration, expected stages such as '(<)', '(#)', '(trim)', '(elide)', '(fence)' or '(template_name)' separated by '|'",
+        ));
+    }
 
-    let (_, link) = delimited(multispace0, link_part, multispace0)
+    // hiding lines starts over from the whole file and eliding bodies
+    // from the selection, either would throw away whatever the stages
+    // before it had done, and the whole file would have none elided
+    let stages = decoration.stages();
+    let text = &input[..input.len() - rem.len()];
+    let stage_error = |index, message| {
+        let at = stage_offset(text, index);
+        Err(ParseError::at(input, &input[at..], message))
+    };
+    let position = |stage: &Stage| stages.iter().position(|s| s == stage);
+    let after_first = |stage: &Stage| {
+        let index = stages.iter().skip(1).position(|s| s == stage)?;
+        Some(index + 1)
+    };
+    if let Some(index) = position(&Stage::Elide)
+        && stages.contains(&Stage::Hidden)
+    {
+        return stage_error(
+            index,
+            "'elide' can't be used with '#' which shows the whole file as it is",
+        );
+    }
+    if let Some(index) = after_first(&Stage::Hidden) {
+        return stage_error(
+            index,
+            "'#' starts over from the whole file so it must be the first stage",
+        );
+    }
+    if let Some(index) = after_first(&Stage::Elide) {
+        return stage_error(
+            index,
+            "'elide' starts over from the selection so it must be the first stage",
+        );
+    }
+
+    let (rem, path) = preceded(multispace0, path_part)
         .parse(rem)
         .finish()
-        .map_err(|err| ParseError(err.into()))?;
+        .map_err(|err| ParseError::at(input, err.input, "missing file path"))?;
 
-    Ok(Anchor { decoration, link })
-}
+    let (path, revision) = revision_part(path);
+    if revision.as_deref() == Some("") {
+        return Err(ParseError::at(input, rem, "expected a revision after '@'"));
+    }
 
-// == Link Parsing
+    let (rem, language) = language_part(rem).finish().map_err(|err| {
+        ParseError::at(input, err.input, "expected '[lang=<name>]' after file path")
+    })?;
 
-fn link_part(source: &str) -> IResult<&str, Link> {
-    (path_part, strategy_part)
-        .map(|(path, strategy)| Link { path, strategy })
-        .parse(source)
+    if rem.starts_with('[') {
+        return Err(ParseError::at(
+            input,
+            rem,
+            "expected '[lang=<name>]' after file path",
+        ));
+    }
+
+    let strategy = match rem.strip_prefix('#') {
+        Some(query) => QueryAnchor::parse(query)
+            .map(Strategy::Query)
+            .map_err(|err| ParseError {
+                offset: input.len() - query.len() + err.offset,
+                message: err.message,
+            })?,
+        None => {
+            let (tail, strategy) = strategy_part
+                .parse(rem)
+                .finish()
+                .map_err(|err| ParseError::at(input, err.input, "unknown strategy after ':'"))?;
+            if !tail.is_empty() {
+                return Err(ParseError::at(
+                    input,
+                    tail,
+                    "unexpected text after line numbers",
+                ));
+            }
+            strategy
+        }
+    };
+
+    Ok(Anchor {
+        decoration,
+        link: Link {
+            path,
+            strategy,
+            language: language.map(String::from),
+            revision,
+        },
+    })
 }
 
 // == Decoration Parsing
 
 fn decoration_part(input: &str) -> IResult<&str, Decoration> {
-    let decoration = alt((leftshift_decoration, template_decoration));
-    opt(delimited(tag("("), decoration, tag(")")))
-        .map(Option::unwrap_or_default)
-        .parse(input)
+    let stage = delimited(multispace0, decoration_stage, multispace0);
+    opt(delimited(
+        tag("("),
+        separated_list1(tag("|"), stage),
+        tag(")"),
+    ))
+    .map(|stages| Decoration(stages.unwrap_or_default()))
+    .parse(input)
 }
 
-fn leftshift_decoration(input: &str) -> IResult<&str, Decoration> {
-    value(Decoration::LeftShift, tag("<")).parse(input)
+/// offset of the nth stage within the text of a decoration
+fn stage_offset(text: &str, index: usize) -> usize {
+    let start = text
+        .match_indices(['(', '|'])
+        .nth(index)
+        .map_or(0, |(at, _)| at + 1);
+    start + text[start..].len() - text[start..].trim_start().len()
 }
 
-fn template_decoration(input: &str) -> IResult<&str, Decoration> {
-    recognize(alphanumeric1)
-        .map(|tpl: &str| Decoration::Template(tpl.to_owned()))
-        .parse(input)
+fn decoration_stage(input: &str) -> IResult<&str, Stage> {
+    alt((leftshift_stage, hidden_stage, named_stage)).parse(input)
+}
+
+fn leftshift_stage(input: &str) -> IResult<&str, Stage> {
+    value(Stage::LeftShift, tag("<")).parse(input)
+}
+
+fn hidden_stage(input: &str) -> IResult<&str, Stage> {
+    value(Stage::Hidden, tag("#")).parse(input)
+}
+
+fn named_stage(input: &str) -> IResult<&str, Stage> {
+    let (rem, name) = alphanumeric1(input)?;
+    match name {
+        "trim" => Ok((rem, Stage::Trim)),
+        "elide" => Ok((rem, Stage::Elide)),
+        "fence" => many0(preceded(tag(","), fence_attribute))
+            .map(|attrs| Stage::Fence(attrs.into_iter().map(String::from).collect()))
+            .parse(rem),
+        tpl => Ok((rem, Stage::Template(tpl.to_owned()))),
+    }
+}
+
+fn fence_attribute(input: &str) -> IResult<&str, &str> {
+    recognize(many1_count(alt((
+        alphanumeric1,
+        tag("_"),
+        tag("-"),
+        tag("="),
+        tag("."),
+    ))))
+    .parse(input)
 }
 
 // == Path Parsing
@@ -61,15 +200,50 @@ fn path_part(input: &str) -> IResult<&str, PathBuf> {
 }
 
 fn path_with_strategy(input: &str) -> IResult<&str, PathBuf> {
-    is_not(":#").map(PathBuf::from).parse(input)
+    is_not(":#[").map(PathBuf::from).parse(input)
 }
 
 fn path_with_no_strategy(input: &str) -> IResult<&str, PathBuf> {
-    terminated(is_not(" "), multispace0)
+    terminated(is_not(" ["), multispace0)
         .map(PathBuf::from)
         .parse(input)
 }
 
+// == Revision Parsing
+
+/// Splits a `path@revision` in two.  Only an `@` in the file name
+/// starts the revision, and not at its start, so paths such as
+/// `node_modules/@types/index.d.ts` or `v@2/lib.rs` are left whole.
+/// A revision with a `/` in it is wrapped, as in `lib.rs@(release/1.2)`,
+/// and a file really named with an `@` escapes it, as in `foo\@bar.rs`.
+fn revision_part(path: PathBuf) -> (PathBuf, Option<String>) {
+    let text = path.to_string_lossy();
+    let unescape = |path: &str| PathBuf::from(path.replace("\\@", "@"));
+    if let Some(wrapped) = text.strip_suffix(')')
+        && let Some(at) = wrapped.rfind("@(")
+        && at > 0
+        && !wrapped[..at].ends_with(['/', '\\'])
+    {
+        return (unescape(&wrapped[..at]), Some(wrapped[at + 2..].to_owned()));
+    }
+
+    let name = text.rfind('/').map_or(0, |slash| slash + 1);
+    let at = text[name..]
+        .rmatch_indices('@')
+        .map(|(at, _)| name + at)
+        .find(|&at| at > name && !text[..at].ends_with('\\'));
+    match at {
+        Some(at) => (unescape(&text[..at]), Some(text[at + 1..].to_owned())),
+        None => (unescape(&text), None),
+    }
+}
+
+// == Language Parsing
+
+fn language_part(input: &str) -> IResult<&str, Option<&str>> {
+    opt(delimited(tag("[lang="), named_token, tag("]"))).parse(input)
+}
+
 // == Strategy Parsing
 
 fn strategy_part(input: &str) -> IResult<&str, Strategy> {
@@ -162,11 +336,73 @@ mod test {
     fn decoration_part_works() {
         let (rem, decoration) = decoration_part("(<)").unwrap();
         assert_eq!(rem, "");
-        assert_eq!(decoration, Decoration::LeftShift);
+        assert_eq!(decoration, Stage::LeftShift.into());
 
         let (rem, decoration) = decoration_part("file.rb#class?name=Foo").unwrap();
         assert_eq!(rem, "file.rb#class?name=Foo");
-        assert_eq!(decoration, Decoration::None);
+        assert_eq!(decoration, Decoration::default());
+    }
+
+    #[test]
+    fn hidden_must_be_the_first_stage() {
+        let anchor = parse("(#|fence) lib.rs#fn?name=main").unwrap();
+        assert_eq!(
+            anchor.decoration.stages(),
+            [Stage::Hidden, Stage::Fence(vec![])]
+        );
+
+        let error = parse("(<|#) lib.rs#fn?name=main").unwrap_err();
+        assert_eq!(error.offset, 3);
+        assert!(error.message.contains("must be the first stage"));
+
+        let error = parse("(# | trim | #) lib.rs").unwrap_err();
+        assert_eq!(error.offset, 12);
+    }
+
+    #[test]
+    fn elide_must_be_the_first_stage() {
+        let anchor = parse("(elide|<) lib.rs#impl?name=Foo").unwrap();
+        assert_eq!(anchor.decoration.stages(), [Stage::Elide, Stage::LeftShift]);
+
+        let error = parse("(<| elide) lib.rs").unwrap_err();
+        assert_eq!(error.offset, 4);
+        assert!(error.message.contains("must be the first stage"));
+
+        let error = parse("(#|elide) lib.rs").unwrap_err();
+        assert_eq!(error.offset, 3);
+        assert!(error.message.contains("can't be used with '#'"));
+
+        let error = parse("(elide|#) lib.rs").unwrap_err();
+        assert_eq!(error.offset, 1);
+        assert!(error.message.contains("can't be used with '#'"));
+    }
+
+    #[test]
+    fn decoration_pipeline_works() {
+        let (rem, decoration) = decoration_part("(<|trim | codeblock) file.rb").unwrap();
+        assert_eq!(rem, " file.rb");
+        assert_eq!(
+            decoration.stages(),
+            [
+                Stage::LeftShift,
+                Stage::Trim,
+                Stage::Template("codeblock".to_owned())
+            ]
+        );
+
+        let (_, decoration) = decoration_part("(elide|fence,ignore,hl_lines=3-5|fenced)").unwrap();
+        assert_eq!(
+            decoration.stages(),
+            [
+                Stage::Elide,
+                Stage::Fence(vec!["ignore".to_owned(), "hl_lines=3-5".to_owned()]),
+                Stage::Template("fenced".to_owned())
+            ]
+        );
+
+        let err = parse("(<|) file.rb").unwrap_err();
+        assert_eq!(err.offset, 0);
+        assert!(err.message.starts_with("unknown decoration"));
     }
 
     #[test]
@@ -184,6 +420,67 @@ mod test {
         assert_eq!(string, PathBuf::from("path.rb"));
     }
 
+    #[test]
+    fn language_override_works() {
+        let anchor = parse("file.txt[lang=ruby]#class?name=Foo").unwrap();
+        assert_eq!(anchor.link.path, PathBuf::from("file.txt"));
+        assert_eq!(anchor.link.language.as_deref(), Some("ruby"));
+        assert!(matches!(anchor.link.strategy, Strategy::Query(_)));
+
+        let anchor = parse("file.txt[lang=ruby]").unwrap();
+        assert_eq!(anchor.link.language.as_deref(), Some("ruby"));
+        assert_eq!(anchor.link.strategy, Strategy::Full);
+
+        let error = parse("file.txt[language=ruby]:1").unwrap_err();
+        assert_eq!(error.offset, 8);
+        assert_eq!(error.message, "expected '[lang=<name>]' after file path");
+    }
+
+    #[test]
+    fn revision_works() {
+        let anchor = parse("src/lib.rs@v1.2#struct?name=Linker").unwrap();
+        assert_eq!(anchor.link.path, PathBuf::from("src/lib.rs"));
+        assert_eq!(anchor.link.revision.as_deref(), Some("v1.2"));
+        assert!(matches!(anchor.link.strategy, Strategy::Query(_)));
+
+        let anchor = parse("lib.rs@(release/1.2)[lang=rust]:3:5").unwrap();
+        assert_eq!(anchor.link.path, PathBuf::from("lib.rs"));
+        assert_eq!(anchor.link.revision.as_deref(), Some("release/1.2"));
+        assert_eq!(anchor.link.language.as_deref(), Some("rust"));
+        assert_eq!(anchor.link.strategy, Strategy::Between { start: 3, end: 5 });
+
+        let anchor = parse("node_modules/@types/index.d.ts").unwrap();
+        assert_eq!(
+            anchor.link.path,
+            PathBuf::from("node_modules/@types/index.d.ts")
+        );
+        assert_eq!(anchor.link.revision, None);
+
+        let anchor = parse("dir@2/file.rs:3").unwrap();
+        assert_eq!(anchor.link.path, PathBuf::from("dir@2/file.rs"));
+        assert_eq!(anchor.link.revision, None);
+
+        let anchor = parse("dir@2/file@v1.rs@main").unwrap();
+        assert_eq!(anchor.link.path, PathBuf::from("dir@2/file@v1.rs"));
+        assert_eq!(anchor.link.revision.as_deref(), Some("main"));
+
+        let anchor = parse(r"foo\@bar.rs:3").unwrap();
+        assert_eq!(anchor.link.path, PathBuf::from("foo@bar.rs"));
+        assert_eq!(anchor.link.revision, None);
+
+        let anchor = parse(r"foo\@bar.rs@(release/1.2)").unwrap();
+        assert_eq!(anchor.link.path, PathBuf::from("foo@bar.rs"));
+        assert_eq!(anchor.link.revision.as_deref(), Some("release/1.2"));
+
+        let anchor = parse(r"foo\@bar.rs@v1").unwrap();
+        assert_eq!(anchor.link.path, PathBuf::from("foo@bar.rs"));
+        assert_eq!(anchor.link.revision.as_deref(), Some("v1"));
+
+        let error = parse("lib.rs@:3").unwrap_err();
+        assert_eq!(error.offset, 7);
+        assert_eq!(error.message, "expected a revision after '@'");
+    }
+
     #[test]
     fn path_with_no_strategy_works() {
         let (rem, path) = path_with_no_strategy("file.rb ").unwrap();
@@ -225,6 +522,27 @@ mod test {
         assert_eq!(strat, Strategy::Named(NamedAnchor::new("_pink-floyd")));
     }
 
+    #[test]
+    fn parse_errors_are_explained() {
+        let error = parse("file.rb:foo bar").unwrap_err();
+        assert_eq!(error.offset, 11);
+        assert_eq!(error.message, "unknown strategy after ':'");
+
+        let error = parse("file.rb:1:2:3").unwrap_err();
+        assert_eq!(error.offset, 11);
+        assert_eq!(error.message, "unexpected text after line numbers");
+
+        let error = parse("(<<) file.rb").unwrap_err();
+        assert_eq!(error.offset, 0);
+
+        let error = parse("file.rb#class?name=Foo Bar").unwrap_err();
+        assert_eq!(error.offset, 22);
+        assert_eq!(
+            error.message,
+            "binding value contains unsupported character"
+        );
+    }
+
     #[test]
     fn parser_full_strategy() {
         let anchor = parse("file.rb").unwrap();
@@ -250,7 +568,14 @@ mod test {
             anchor.link.strategy,
             Strategy::Named(NamedAnchor::new("block1"))
         );
-        assert_eq!(anchor.decoration, Decoration::LeftShift);
+        assert_eq!(anchor.decoration, Stage::LeftShift.into());
+    }
+
+    #[test]
+    fn parse_with_hidden() {
+        let anchor = parse("(#) file.rs#fn?name=main").unwrap();
+        assert_eq!(anchor.link.path, PathBuf::from("file.rs"));
+        assert_eq!(anchor.decoration, Stage::Hidden.into());
     }
 
     #[test]
@@ -263,7 +588,7 @@ mod test {
         );
         assert_eq!(
             anchor.decoration,
-            Decoration::Template("codeblock".to_owned())
+            Stage::Template("codeblock".to_owned()).into()
         );
     }
 
@@ -306,5 +631,11 @@ mod test {
             anchor.link.strategy,
             Strategy::Query(QueryAnchor::parse("class?name=Foo").unwrap())
         );
+
+        let anchor = parse(r#"file.rb#class?name="Foo Bar" "#).unwrap();
+        let Strategy::Query(query) = anchor.link.strategy else {
+            panic!("expected a query strategy");
+        };
+        assert_eq!(query.bindings.get("name").unwrap(), "Foo Bar");
     }
 }
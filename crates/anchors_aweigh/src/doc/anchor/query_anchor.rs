@@ -1,68 +1,191 @@
 //! Query Anchor
 
 use nom::branch::alt;
-use nom::bytes::complete::{tag, take_until};
-use nom::character::complete::alphanumeric1;
-use nom::combinator::{eof, rest, value};
-use nom::error::Error;
-use nom::multi::fold_many0;
-use nom::sequence::{preceded, separated_pair, terminated};
+use nom::bytes::complete::{tag, take_till};
+use nom::character::complete::{alphanumeric1, digit1};
+use nom::combinator::{map_res, opt, recognize, rest, value, verify};
+use nom::multi::many1_count;
+use nom::sequence::{delimited, preceded};
 use nom::{Finish, IResult, Parser};
 use serde::{Deserialize, Serialize};
-use std::collections::BTreeMap;
+use std::fmt;
+
+mod binding;
+
+pub use binding::{BindingValue, Bindings};
 
 #[derive(Debug, thiserror::Error)]
-#[error("{0:?}")]
-pub struct ParseQueryAnchorError(#[from] Error<String>);
+#[error("{message} at offset {offset}")]
+pub struct ParseQueryAnchorError {
+    /// byte offset into the query where parsing failed
+    pub offset: usize,
 
-type Bindings = BTreeMap<String, String>;
+    /// human explanation of what went wrong
+    pub message: String,
+}
+
+impl ParseQueryAnchorError {
+    fn at(input: &str, rem: &str) -> Self {
+        let offset = input.len() - rem.len();
+        Self {
+            offset,
+            message: explain(&input[..offset], rem).to_owned(),
+        }
+    }
+}
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct QueryAnchor {
     pub name: String,
+    pub matches: MatchSelector,
+    pub capture: Option<String>,
+    pub extract: Extract,
     pub bindings: Bindings,
 }
 
+/// Which of the matches found by a query should be
+/// selected, indicated by `[...]` after the query name
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum MatchSelector {
+    /// the query is expected to match only once, the
+    /// first match is taken and a warning is logged
+    /// if there happen to be more
+    #[default]
+    Unique,
+
+    /// take only the nth match found, starting from one,
+    /// indicated by `[<number>]`
+    Nth(usize),
+
+    /// take every match found, indicated by `[*]`
+    All,
+}
+
+/// How the content of a selected match is pulled
+/// from the source file
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Extract {
+    /// every line the match touches, in full
+    #[default]
+    Lines,
+
+    /// only the exact bytes of the match, even when it
+    /// starts or ends part way through a line, indicated
+    /// by a trailing `!` after the query name or capture
+    Exact,
+}
+
 impl QueryAnchor {
     pub fn parse(input: &str) -> Result<Self, ParseQueryAnchorError> {
-        let (_, anchor) = query_anchor
+        let (rem, (name, matches, capture, extract)) = anchor_head
             .parse(input)
             .finish()
-            .map_err(|err| ParseQueryAnchorError(err.into()))?;
+            .map_err(|err| ParseQueryAnchorError::at(input, err.input))?;
+
+        if name.is_empty() {
+            return Err(ParseQueryAnchorError::at(input, input));
+        }
 
-        Ok(anchor)
+        let bindings = match rem.strip_prefix('?') {
+            Some(bindings) => binding::parse(bindings).map_err(|err| ParseQueryAnchorError {
+                offset: input.len() - bindings.len() + err.offset,
+                message: err.message.to_owned(),
+            })?,
+            None if rem.is_empty() => Bindings::default(),
+            None => return Err(ParseQueryAnchorError::at(input, rem)),
+        };
+
+        Ok(Self {
+            name,
+            matches,
+            capture,
+            extract,
+            bindings,
+        })
+    }
+}
+
+/// Writes the query back out in the same form it is parsed
+impl fmt::Display for QueryAnchor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+        match self.matches {
+            MatchSelector::Unique => (),
+            MatchSelector::Nth(nth) => write!(f, "[{nth}]")?,
+            MatchSelector::All => write!(f, "[*]")?,
+        }
+        if let Some(capture) = &self.capture {
+            write!(f, "@{capture}")?;
+        }
+        if self.extract == Extract::Exact {
+            write!(f, "!")?;
+        }
+        let values = self
+            .bindings
+            .iter()
+            .flat_map(|(key, value)| value.values().iter().map(move |value| (key, value)));
+        for (index, (key, value)) in values.enumerate() {
+            let separator = if index == 0 { '?' } else { '&' };
+            write!(f, "{separator}{key}={}", binding::DisplayValue(value))?;
+        }
+        Ok(())
+    }
+}
+
+/// best guess at why parsing stopped given what
+/// was parsed before and what could not be
+fn explain(parsed: &str, rem: &str) -> &'static str {
+    if parsed.is_empty() {
+        return "missing query name after '#'";
+    }
+    match rem.chars().next() {
+        Some('[') => "match selector must be '[*]' or a number starting from one",
+        Some('@') => "capture name may only contain letters, numbers and underscores",
+        Some('!') => "'!' must come after the query name or capture",
+        _ => "unexpected character in query",
     }
 }
 
 pub(super) fn query_anchor(input: &str) -> IResult<&str, QueryAnchor> {
-    (anchor_name, anchor_bindings)
-        .map(|(name, bindings)| QueryAnchor { name, bindings })
-        .parse(input)
+    map_res(recognize((anchor_head, rest)), QueryAnchor::parse).parse(input)
+}
+
+fn anchor_head(input: &str) -> IResult<&str, (String, MatchSelector, Option<String>, Extract)> {
+    (anchor_name, anchor_matches, anchor_capture, anchor_extract).parse(input)
 }
 
 fn anchor_name(input: &str) -> IResult<&str, String> {
-    alt((take_until("?"), rest)).map(String::from).parse(input)
+    take_till(|c| matches!(c, '?' | '[' | '@' | '!'))
+        .map(String::from)
+        .parse(input)
 }
 
-fn anchor_bindings(input: &str) -> IResult<&str, Bindings> {
-    let target = alt((terminated(alphanumeric1, tag("&")), alphanumeric1));
-    alt((
-        value(Bindings::default(), eof),
-        preceded(
-            tag("?"),
-            fold_many0(
-                separated_pair(alphanumeric1, tag("="), target),
-                Bindings::default,
-                |mut hashmap, (left, right)| {
-                    hashmap.insert(String::from(left), String::from(right));
-                    hashmap
-                },
-            ),
-        ),
+fn anchor_capture(input: &str) -> IResult<&str, Option<String>> {
+    opt(preceded(
+        tag("@"),
+        recognize(many1_count(alt((alphanumeric1, tag("_"))))),
     ))
+    .map(|capture| capture.map(String::from))
     .parse(input)
 }
 
+fn anchor_extract(input: &str) -> IResult<&str, Extract> {
+    opt(value(Extract::Exact, tag("!")))
+        .map(Option::unwrap_or_default)
+        .parse(input)
+}
+
+fn anchor_matches(input: &str) -> IResult<&str, MatchSelector> {
+    let nth = verify(map_res(digit1, str::parse), |nth: &usize| *nth > 0);
+    let selector = alt((
+        value(MatchSelector::All, tag("*")),
+        nth.map(MatchSelector::Nth),
+    ));
+    opt(delimited(tag("["), selector, tag("]")))
+        .map(Option::unwrap_or_default)
+        .parse(input)
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -75,6 +198,85 @@ mod test {
 
         let anchor = QueryAnchor::parse("mod").unwrap();
         assert_eq!(anchor.name, "mod");
+        assert_eq!(anchor.matches, MatchSelector::Unique);
         assert!(anchor.bindings.is_empty());
     }
+
+    #[test]
+    fn parsing_match_selectors() {
+        let anchor = QueryAnchor::parse("fn[2]?name=new").unwrap();
+        assert_eq!(anchor.name, "fn");
+        assert_eq!(anchor.matches, MatchSelector::Nth(2));
+        assert_eq!(anchor.bindings.get("name").unwrap(), "new");
+
+        let anchor = QueryAnchor::parse("fn[*]").unwrap();
+        assert_eq!(anchor.name, "fn");
+        assert_eq!(anchor.matches, MatchSelector::All);
+
+        assert!(QueryAnchor::parse("fn[0]?name=new").is_err());
+    }
+
+    #[test]
+    fn display_round_trips() {
+        for input in [
+            "mod",
+            "fn[2]@signature!?name=new",
+            "pair[*]?key=a&value=b",
+            r#"fn?name=Foo::Bar&name=snake-case&title="with \"space\"""#,
+        ] {
+            assert_eq!(QueryAnchor::parse(input).unwrap().to_string(), input);
+        }
+    }
+
+    #[test]
+    fn parsing_rich_bindings() {
+        let anchor = QueryAnchor::parse(r#"fn?name=my_fn&key=a%2Fb&title="x y""#).unwrap();
+        assert_eq!(anchor.bindings.get("name").unwrap(), "my_fn");
+        assert_eq!(anchor.bindings.get("key").unwrap(), "a/b");
+        assert_eq!(anchor.bindings.get("title").unwrap(), "x y");
+
+        let error = QueryAnchor::parse("fn?name=a&&").unwrap_err();
+        assert_eq!(error.offset, 10);
+    }
+
+    #[test]
+    fn parse_errors_are_explained() {
+        let error = QueryAnchor::parse("fn?name=new thing").unwrap_err();
+        assert_eq!(error.offset, 11);
+        assert_eq!(
+            error.message,
+            "binding value contains unsupported character"
+        );
+
+        let error = QueryAnchor::parse("fn?na-me=new").unwrap_err();
+        assert_eq!(error.offset, 3);
+        assert_eq!(
+            error.message,
+            "binding key contains unsupported character or is missing '='"
+        );
+
+        let error = QueryAnchor::parse("fn[x]").unwrap_err();
+        assert_eq!(error.offset, 2);
+
+        let error = QueryAnchor::parse("").unwrap_err();
+        assert_eq!(error.message, "missing query name after '#'");
+    }
+
+    #[test]
+    fn parsing_capture_and_extract() {
+        let anchor = QueryAnchor::parse("fn[2]@signature!?name=new").unwrap();
+        assert_eq!(anchor.name, "fn");
+        assert_eq!(anchor.matches, MatchSelector::Nth(2));
+        assert_eq!(anchor.capture.as_deref(), Some("signature"));
+        assert_eq!(anchor.extract, Extract::Exact);
+        assert_eq!(anchor.bindings.get("name").unwrap(), "new");
+
+        let anchor = QueryAnchor::parse("fn@body").unwrap();
+        assert_eq!(anchor.capture.as_deref(), Some("body"));
+        assert_eq!(anchor.extract, Extract::Lines);
+
+        let anchor = QueryAnchor::parse("fn!").unwrap();
+        assert_eq!(anchor.capture, None);
+        assert_eq!(anchor.extract, Extract::Exact);
+    }
 }
@@ -0,0 +1,275 @@
+//! Query Anchor Bindings
+//!
+//! The `key=value&key=value` portion following the `?` of a
+//! query anchor.  Values are either bare, where `%XX` escapes
+//! are decoded, or wrapped in double quotes where `\"` and
+//! `\\` are the only escapes.  Repeating a key builds a list.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+
+pub type Bindings = BTreeMap<String, BindingValue>;
+
+/// Value bound to a key, rendered into the query template
+/// as either a string or, when the key repeats, a list
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum BindingValue {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl BindingValue {
+    /// every value bound, in the order they were given
+    pub fn values(&self) -> &[String] {
+        match self {
+            Self::One(value) => std::slice::from_ref(value),
+            Self::Many(values) => values,
+        }
+    }
+
+    fn push(&mut self, value: String) {
+        match self {
+            Self::One(first) => *self = Self::Many(vec![std::mem::take(first), value]),
+            Self::Many(values) => values.push(value),
+        }
+    }
+}
+
+impl PartialEq<str> for BindingValue {
+    fn eq(&self, other: &str) -> bool {
+        matches!(self, Self::One(value) if value == other)
+    }
+}
+
+impl From<&str> for BindingValue {
+    fn from(value: &str) -> Self {
+        Self::One(value.to_owned())
+    }
+}
+
+/// Writes each value so that it parses back the same, quoting
+/// any which hold characters a bare value cannot
+pub(super) struct DisplayValue<'a>(pub &'a str);
+
+impl fmt::Display for DisplayValue<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bare = !self.0.is_empty() && !self.0.contains(needs_quotes);
+        if bare {
+            return f.write_str(self.0);
+        }
+        f.write_str("\"")?;
+        for c in self.0.chars() {
+            if matches!(c, '"' | '\\') {
+                f.write_str("\\")?;
+            }
+            write!(f, "{c}")?;
+        }
+        f.write_str("\"")
+    }
+}
+
+fn needs_quotes(c: char) -> bool {
+    c.is_whitespace() || matches!(c, '&' | '"' | '%')
+}
+
+fn key_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+/// where and why bindings failed to parse
+#[derive(Debug)]
+pub(super) struct BindingError {
+    pub offset: usize,
+    pub message: &'static str,
+}
+
+impl BindingError {
+    fn at(offset: usize, message: &'static str) -> Self {
+        Self { offset, message }
+    }
+}
+
+/// parses the bindings following a `?`, offsets in any
+/// error are relative to the start of `input`
+pub(super) fn parse(input: &str) -> Result<Bindings, BindingError> {
+    let mut bindings = Bindings::new();
+    let mut pos = 0;
+
+    loop {
+        let start = pos;
+        let key_len = input[pos..]
+            .find(|c| !key_char(c))
+            .unwrap_or(input.len() - pos);
+        let key = &input[pos..pos + key_len];
+        pos += key_len;
+
+        if key.is_empty() && pos == input.len() {
+            return Err(BindingError::at(pos, "expected a binding after '?' or '&'"));
+        }
+        if key.is_empty() || !input[pos..].starts_with('=') {
+            return Err(BindingError::at(
+                start,
+                "binding key contains unsupported character or is missing '='",
+            ));
+        }
+        pos += 1;
+
+        let value = match input[pos..].starts_with('"') {
+            true => quoted_value(&input[pos..]),
+            false => bare_value(&input[pos..]),
+        };
+        let (value, len) = value.map_err(|err| BindingError {
+            offset: pos + err.offset,
+            ..err
+        })?;
+        pos += len;
+
+        match bindings.get_mut(key) {
+            Some(existing) => existing.push(value),
+            None => {
+                bindings.insert(key.to_owned(), BindingValue::One(value));
+            }
+        }
+
+        match input[pos..].chars().next() {
+            None => return Ok(bindings),
+            Some('&') => pos += 1,
+            Some(_) => {
+                return Err(BindingError::at(
+                    pos,
+                    "expected '&' after quoted binding value",
+                ));
+            }
+        }
+    }
+}
+
+/// value up to the next `&` with `%XX` escapes decoded,
+/// giving the value and how many bytes were consumed
+fn bare_value(input: &str) -> Result<(String, usize), BindingError> {
+    let len = input.find('&').unwrap_or(input.len());
+    let raw = &input[..len];
+
+    if raw.is_empty() {
+        return Err(BindingError::at(0, "binding value is missing"));
+    }
+    if let Some(at) = raw.find(|c: char| c.is_whitespace() || c == '"') {
+        return Err(BindingError::at(
+            at,
+            "binding value contains unsupported character",
+        ));
+    }
+
+    let mut bytes = Vec::with_capacity(raw.len());
+    let mut iter = raw.bytes().enumerate();
+    while let Some((at, byte)) = iter.next() {
+        if byte != b'%' {
+            bytes.push(byte);
+            continue;
+        }
+        let decoded = raw
+            .get(at + 1..at + 3)
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            .ok_or(BindingError::at(
+                at,
+                "invalid percent-encoding in binding value, expected '%' and two hex digits",
+            ))?;
+        bytes.push(decoded);
+        iter.nth(1);
+    }
+
+    String::from_utf8(bytes)
+        .map(|value| (value, len))
+        .map_err(|_| BindingError::at(0, "percent-encoded binding value is not valid UTF-8"))
+}
+
+/// value within double quotes, `input` starting at the opening
+/// quote, giving the value and bytes consumed through the closing quote
+fn quoted_value(input: &str) -> Result<(String, usize), BindingError> {
+    let mut value = String::new();
+    let mut chars = input.char_indices().skip(1);
+    while let Some((at, c)) = chars.next() {
+        match c {
+            '"' => return Ok((value, at + 1)),
+            '\\' => match chars.next() {
+                Some((_, escaped @ ('"' | '\\'))) => value.push(escaped),
+                _ => {
+                    return Err(BindingError::at(
+                        at,
+                        "unknown escape in quoted binding value, expected '\\\"' or '\\\\'",
+                    ));
+                }
+            },
+            c => value.push(c),
+        }
+    }
+    Err(BindingError::at(0, "unclosed '\"' in binding value"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn value(bindings: &Bindings, key: &str) -> Vec<String> {
+        bindings.get(key).unwrap().values().to_vec()
+    }
+
+    #[test]
+    fn bare_values_allow_punctuation() {
+        let bindings = parse("name=Foo::Bar&key=package.version&case=snake-case_ok").unwrap();
+        assert_eq!(value(&bindings, "name"), ["Foo::Bar"]);
+        assert_eq!(value(&bindings, "key"), ["package.version"]);
+        assert_eq!(value(&bindings, "case"), ["snake-case_ok"]);
+    }
+
+    #[test]
+    fn quoted_and_encoded_values() {
+        let bindings = parse(r#"title="with space \"quoted\""&amp=a%26b%20c"#).unwrap();
+        assert_eq!(value(&bindings, "title"), [r#"with space "quoted""#]);
+        assert_eq!(value(&bindings, "amp"), ["a&b c"]);
+    }
+
+    #[test]
+    fn repeated_keys_become_lists() {
+        let bindings = parse("name=a&other=x&name=b&name=c").unwrap();
+        assert_eq!(
+            bindings.get("name"),
+            Some(&BindingValue::Many(vec![
+                "a".into(),
+                "b".into(),
+                "c".into()
+            ]))
+        );
+        assert_eq!(bindings.get("other").unwrap(), "x");
+    }
+
+    #[test]
+    fn malformed_bindings_are_errors() {
+        let cases = [
+            ("", 0, "expected a binding"),
+            ("name=a&", 7, "expected a binding"),
+            ("name", 0, "missing '='"),
+            ("name=", 5, "value is missing"),
+            ("name=a b", 6, "unsupported character"),
+            ("name=a%2", 6, "invalid percent-encoding"),
+            ("name=%FF", 5, "not valid UTF-8"),
+            ("name=\"open", 5, "unclosed"),
+            ("name=\"a\\n\"", 7, "unknown escape"),
+            ("name=\"a\"b", 8, "expected '&'"),
+        ];
+        for (input, offset, message) in cases {
+            let error = parse(input).unwrap_err();
+            assert!(error.message.contains(message), "{input}: {error:?}");
+            assert_eq!(error.offset, offset, "{input}");
+        }
+    }
+
+    #[test]
+    fn display_quotes_when_needed() {
+        assert_eq!(DisplayValue("Foo::Bar").to_string(), "Foo::Bar");
+        assert_eq!(DisplayValue("a b").to_string(), "\"a b\"");
+        assert_eq!(DisplayValue("say \"hi\"").to_string(), r#""say \"hi\"""#);
+    }
+}
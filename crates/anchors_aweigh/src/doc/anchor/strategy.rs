@@ -1,6 +1,18 @@
-use super::{NamedAnchor, QueryAnchor};
-use crate::{linker::Linker, source::File};
+use super::{Extract, MatchSelector, NamedAnchor, QueryAnchor};
+use crate::linker::{LinkError, Linker};
+use crate::source::{File, SourceRange};
 use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::ops::RangeInclusive;
+
+/// Content a [Strategy] selected from a source
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Selection {
+    pub contents: String,
+    /// ranges of the source the contents were taken from, which
+    /// are what a [Stage::Hidden](super::Stage) leaves visible
+    pub ranges: Vec<SourceRange>,
+}
 
 /// Describes how to select lines from a file for the
 /// subject of analysis and rendering
@@ -37,7 +49,60 @@ pub enum Strategy {
 }
 
 impl Strategy {
-    pub fn find_content(&self, source: &File, linker: &Linker) -> Option<String> {
+    /// Content selected from the source, `None` when the strategy
+    /// selects nothing.  Fails when a query anchor doesn't fit the
+    /// query it names or the query itself can't be run.
+    pub fn find_content(
+        &self,
+        source: &File,
+        linker: &Linker,
+    ) -> Result<Option<String>, LinkError> {
+        Ok(self
+            .find_selection(source, linker)?
+            .map(|selection| selection.contents))
+    }
+
+    /// Content selected from the source along with the ranges of
+    /// the source it was taken from, see [Self::find_content]
+    pub fn find_selection(
+        &self,
+        source: &File,
+        linker: &Linker,
+    ) -> Result<Option<Selection>, LinkError> {
+        match self {
+            Self::Query(anchor) => query_selection(anchor, source, linker),
+            _ => Ok(self.line_content(source).zip(self.line_span(source)).map(
+                |(contents, lines)| Selection {
+                    contents,
+                    ranges: vec![SourceRange::from_lines(&source.contents, lines)],
+                },
+            )),
+        }
+    }
+
+    /// first and last line selected by strategies
+    /// which select a single run of lines
+    fn line_span(&self, source: &File) -> Option<RangeInclusive<usize>> {
+        let count = source.contents.lines().count();
+        match self {
+            Self::Full => Some(1..=count),
+            Self::Between { start, end } => Some(*start..=*end),
+            Self::HereDown(line) => Some(*line..=count),
+            Self::DownTo(line) => Some(1..=*line),
+            Self::ThisLine(line) => Some(*line..=*line),
+            Self::Named(anchor) => {
+                let mut lines = source.contents.lines().enumerate();
+                let (start, _) = lines.find(|(_, line)| !anchor.missing_start(line))?;
+                let end = lines
+                    .find(|(_, line)| !anchor.missing_end(line))
+                    .map_or(count, |(end, _)| end);
+                Some(start + 2..=end)
+            }
+            Self::Query(_) => None,
+        }
+    }
+
+    fn line_content(&self, source: &File) -> Option<String> {
         match self {
             Self::Full => Some(source.contents.clone()),
             Self::Between { start, end } => {
@@ -76,11 +141,10 @@ impl Strategy {
             }
             Self::Named(anchor) => {
                 let mut string = String::new();
-                let lines = source.contents.lines();
-                let filtered = lines
-                    .skip_while(|line| anchor.missing_start(line))
-                    .skip(1)
-                    .take_while(|line| anchor.missing_end(line));
+                let mut lines = source.contents.lines().peekable();
+                while lines.next_if(|line| anchor.missing_start(line)).is_some() {}
+                lines.peek()?;
+                let filtered = lines.skip(1).take_while(|line| anchor.missing_end(line));
                 for line in filtered {
                     string.push_str(line);
                     string.push('\n');
@@ -88,11 +152,250 @@ impl Strategy {
                 string.pop();
                 Some(string)
             }
-            Self::Query(anchor) => {
-                let query = linker.queries.fetch(source.language?, &anchor.name)?;
-                let range = query.find(source, &anchor.bindings).unwrap()?;
-                Some(range.fetch_lines(source))
-            }
+            // see query_selection
+            Self::Query(_) => None,
         }
     }
 }
+
+fn query_selection(
+    anchor: &QueryAnchor,
+    source: &File,
+    linker: &Linker,
+) -> Result<Option<Selection>, LinkError> {
+    let ranges = query_ranges(anchor, source, linker)?;
+    if anchor.matches == MatchSelector::Unique && ranges.len() > 1 {
+        ::log::warn!(
+            "query [{}] matched {} times in {:?}, using the first",
+            anchor.name,
+            ranges.len(),
+            source.path
+        );
+    }
+
+    let selected = select(anchor, ranges);
+    if selected.is_empty() {
+        return Ok(None);
+    }
+    let contents: Vec<String> = selected
+        .iter()
+        .map(|range| match anchor.extract {
+            Extract::Lines => range.fetch_lines(source),
+            Extract::Exact => range.fetch_bytes(source),
+        })
+        .collect();
+    Ok(Some(Selection {
+        contents: contents.join(&linker.settings.match_separator),
+        ranges: selected,
+    }))
+}
+
+/// every range the query finds in the source, no matter
+/// which of the matches the anchor goes on to select
+fn query_ranges(
+    anchor: &QueryAnchor,
+    source: &File,
+    linker: &Linker,
+) -> Result<Vec<SourceRange>, LinkError> {
+    let Some(query) = source
+        .language
+        .as_ref()
+        .and_then(|lang| linker.queries.fetch(lang, &anchor.name))
+    else {
+        return Ok(Vec::new());
+    };
+    let failed = |error| LinkError::Query {
+        path: source.path.clone(),
+        query: anchor.to_string(),
+        error: Box::new(error),
+    };
+
+    query
+        .check_bindings(anchor.bindings.keys().map(String::as_str))
+        .map_err(failed)?;
+    let capture = anchor.capture.as_deref();
+    match &linker.cache {
+        Some(cache) => cache.find_all(query, source, capture, &anchor.bindings),
+        None => query.find_all(source, capture, &anchor.bindings),
+    }
+    .map_err(failed)
+}
+
+fn select(anchor: &QueryAnchor, ranges: Vec<SourceRange>) -> Vec<SourceRange> {
+    match anchor.matches {
+        MatchSelector::Unique => ranges.into_iter().take(1).collect(),
+        MatchSelector::Nth(nth) => ranges.into_iter().skip(nth - 1).take(1).collect(),
+        MatchSelector::All => ranges,
+    }
+}
+
+/// Writes the strategy as it appears after the path in an anchor
+impl fmt::Display for Strategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Named(anchor) => write!(f, ":{}", anchor.name()),
+            Self::Full => Ok(()),
+            Self::Between { start, end } => write!(f, ":{start}:{end}"),
+            Self::HereDown(line) => write!(f, ":{line}:"),
+            Self::DownTo(line) => write!(f, "::{line}"),
+            Self::ThisLine(line) => write!(f, ":{line}"),
+            Self::Query(anchor) => write!(f, "#{anchor}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::source::{Language, QueryError, SourceList};
+    use crate::support::fixtures;
+
+    fn linker() -> Linker {
+        let mut linker = Linker::default();
+        let query = Language::Ruby
+            .build_query(r#"(method name: (identifier) @name (#eq? @name "{{name}}")) @match"#)
+            .unwrap();
+        linker.queries.register("method", query);
+        let query = Language::Ruby
+            .build_query(
+                r#"((class name: (constant) @name) @match (#any-of? @name {{#each name}}"{{this}}" {{/each}}))"#,
+            )
+            .unwrap();
+        linker.queries.register("classes", query);
+        linker.settings.match_separator = String::from("\n---\n");
+        linker
+    }
+
+    #[test]
+    fn query_match_selection() {
+        let linker = linker();
+        let source = SourceList::default()
+            .fetch(fixtures::sample_ruby_filename())
+            .unwrap();
+        let strategy = |input: &str| Strategy::Query(QueryAnchor::parse(input).unwrap());
+
+        let first = strategy("method?name=initialize")
+            .find_content(&source, &linker)
+            .unwrap()
+            .unwrap();
+        assert!(first.contains("@name = name"));
+
+        let second = strategy("method[2]?name=initialize")
+            .find_content(&source, &linker)
+            .unwrap()
+            .unwrap();
+        assert!(second.contains("@size = size"));
+
+        let all = strategy("method[*]?name=initialize")
+            .find_content(&source, &linker)
+            .unwrap()
+            .unwrap();
+        assert_eq!(all, format!("{first}\n---\n{second}"));
+
+        assert!(
+            strategy("method[3]?name=initialize")
+                .find_content(&source, &linker)
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn selections_keep_their_source_ranges() {
+        let linker = linker();
+        let source = SourceList::default()
+            .fetch(fixtures::sample_ruby_filename())
+            .unwrap();
+
+        let anchor = QueryAnchor::parse("method[*]?name=initialize").unwrap();
+        let selection = Strategy::Query(anchor)
+            .find_selection(&source, &linker)
+            .unwrap()
+            .unwrap();
+        assert_eq!(selection.ranges.len(), 2);
+        assert!(selection.ranges[0].end < selection.ranges[1].start);
+
+        let selection = Strategy::Between { start: 2, end: 3 }
+            .find_selection(&source, &linker)
+            .unwrap()
+            .unwrap();
+        let [range] = &selection.ranges[..] else {
+            panic!("expected a single range, got {:?}", selection.ranges);
+        };
+        assert_eq!((range.start, range.end), (2, 3));
+        assert_eq!(range.fetch_bytes(&source), selection.contents);
+    }
+
+    #[test]
+    fn repeated_bindings_render_as_lists() {
+        let linker = linker();
+        let source = SourceList::default()
+            .fetch(fixtures::sample_ruby_filename())
+            .unwrap();
+        let anchor = QueryAnchor::parse("classes[*]?name=Foo&name=Bar").unwrap();
+        let content = Strategy::Query(anchor)
+            .find_content(&source, &linker)
+            .unwrap()
+            .unwrap();
+        assert!(content.contains("class Foo"));
+        assert!(content.contains("class Bar"));
+    }
+
+    #[test]
+    fn query_bindings_must_fit_the_query() {
+        let linker = linker();
+        let source = SourceList::default()
+            .fetch(fixtures::sample_ruby_filename())
+            .unwrap();
+        let strategy = |input: &str| Strategy::Query(QueryAnchor::parse(input).unwrap());
+
+        let error = strategy("method")
+            .find_content(&source, &linker)
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            LinkError::Query { ref error, .. } if matches!(**error, QueryError::MissingBinding(_))
+        ));
+        assert!(
+            error
+                .to_string()
+                .ends_with("#method: query needs a value for `name`, such as `?name=...`")
+        );
+
+        let error = strategy("method?name=initialize&nmae=x")
+            .find_content(&source, &linker)
+            .unwrap_err();
+        assert!(error.to_string().contains("no binding named `nmae`"));
+    }
+
+    #[test]
+    fn missing_named_anchor_selects_nothing() {
+        let linker = linker();
+        let source = SourceList::default()
+            .fetch(fixtures::sample_ruby_filename())
+            .unwrap();
+        let strategy = Strategy::Named(NamedAnchor::new("nope"));
+        assert!(strategy.find_content(&source, &linker).unwrap().is_none());
+    }
+
+    #[test]
+    fn query_capture_extraction() {
+        let linker = linker();
+        let source = SourceList::default()
+            .fetch(fixtures::sample_ruby_filename())
+            .unwrap();
+        let strategy = |input: &str| Strategy::Query(QueryAnchor::parse(input).unwrap());
+
+        let lines = strategy("method@name?name=initialize")
+            .find_content(&source, &linker)
+            .unwrap()
+            .unwrap();
+        assert_eq!(lines, "    def initialize(name)");
+
+        let exact = strategy("method[*]@name!?name=initialize")
+            .find_content(&source, &linker)
+            .unwrap()
+            .unwrap();
+        assert_eq!(exact, "initialize\n---\ninitialize");
+    }
+}
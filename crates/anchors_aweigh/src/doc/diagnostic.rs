@@ -0,0 +1,105 @@
+use std::fmt;
+use std::path::PathBuf;
+
+/// Problem found at a specific spot in a document, displayed
+/// much like rustc does with a caret under the offending text
+///
+/// ```text
+/// unknown strategy after ':'
+///  --> docs/chapter.md:3:15
+///   |
+/// 3 | {{#aa file.rs:foo bar}}
+///   |               ^
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub struct Diagnostic {
+    /// document the problem was found in
+    pub path: PathBuf,
+
+    /// line of the problem, starting from one
+    pub line: usize,
+
+    /// column of the problem in characters, starting from one
+    pub column: usize,
+
+    /// full text of the line with the problem
+    pub source_line: String,
+
+    /// human explanation of what went wrong
+    pub message: String,
+}
+
+impl Diagnostic {
+    /// Locates the byte offset within the source and
+    /// builds a diagnostic pointing at it
+    pub fn at_offset<P, M>(path: P, source: &str, offset: usize, message: M) -> Self
+    where
+        P: Into<PathBuf>,
+        M: Into<String>,
+    {
+        let offset = offset.min(source.len());
+        let before = &source[..offset];
+        let line_start = before.rfind('\n').map_or(0, |at| at + 1);
+        let line_end = source[offset..]
+            .find('\n')
+            .map_or(source.len(), |at| at + offset);
+
+        Self {
+            path: path.into(),
+            line: before.matches('\n').count() + 1,
+            column: source[line_start..offset].chars().count() + 1,
+            source_line: source[line_start..line_end]
+                .trim_end_matches('\r')
+                .to_owned(),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let gutter = " ".repeat(self.line.to_string().len());
+        let padding: String = self
+            .source_line
+            .chars()
+            .take(self.column - 1)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+
+        writeln!(f, "{}", self.message)?;
+        writeln!(
+            f,
+            "{gutter}--> {}:{}:{}",
+            self.path.display(),
+            self.line,
+            self.column
+        )?;
+        writeln!(f, "{gutter} |")?;
+        writeln!(f, "{} | {}", self.line, self.source_line)?;
+        write!(f, "{gutter} | {padding}^")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn locates_offsets() {
+        let source = "# Title\n\n{{#aa file.rs:foo bar}}\n";
+        let diagnostic = Diagnostic::at_offset("doc.md", source, 23, "unknown strategy");
+        assert_eq!(diagnostic.line, 3);
+        assert_eq!(diagnostic.column, 15);
+        assert_eq!(diagnostic.source_line, "{{#aa file.rs:foo bar}}");
+    }
+
+    #[test]
+    fn displays_with_a_caret() {
+        let source = "{{#aa file.rs:foo bar}}";
+        let diagnostic = Diagnostic::at_offset("doc.md", source, 14, "unknown strategy");
+        assert_eq!(
+            diagnostic.to_string(),
+            "unknown strategy\n --> doc.md:1:15\n  |\n1 | {{#aa file.rs:foo bar}}\n  |               ^"
+        );
+    }
+}
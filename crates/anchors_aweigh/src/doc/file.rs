@@ -1,4 +1,4 @@
-use super::{Anchor, DocResult, Token};
+use super::{Anchor, Diagnostic, DocError, DocResult, ParseOptions, Stage, Token};
 use std::path::PathBuf;
 
 /// Represents a file that has been parsed into
@@ -18,32 +18,66 @@ pub struct DocFile {
 
 impl DocFile {
     pub fn with_path_and_source<T, S>(path: T, source: S) -> DocResult<Self>
+    where
+        T: Into<PathBuf>,
+        S: Into<String>,
+    {
+        Self::with_options(path, source, &ParseOptions::default())
+    }
+
+    /// Same as [DocFile::with_path_and_source] with control
+    /// over which `{{#aa ...}}` tags are left verbatim
+    pub fn with_options<T, S>(path: T, source: S, options: &ParseOptions) -> DocResult<Self>
     where
         T: Into<PathBuf>,
         S: Into<String>,
     {
         let source = source.into();
         let path: PathBuf = path.into();
-        let mut ast = Token::parse_tokens(&source)?;
+        let located = Token::parse_located(&source, options)
+            .map_err(|err| Diagnostic::at_offset(&path, &source, err.offset, err.message))?;
 
-        // TODO: Need a better and easier to understand
-        //       method of building these anchors
-        for token in &mut ast {
-            if let Token::RawAnchor(data) = token {
-                let mut anchor = Anchor::parse(data)?;
-                if anchor.link.path.is_relative() {
-                    let root = path.parent().unwrap();
-                    anchor.link.path = root.join(anchor.link.path);
+        let mut ast = Vec::with_capacity(located.len());
+        for (offset, token) in located {
+            let (data, rustdoc) = match token {
+                Token::RawAnchor(data) => (data, false),
+                Token::RawInclude { data, rustdoc } => (data, rustdoc),
+                token => {
+                    ast.push(token);
+                    continue;
                 }
-                *token = Token::Anchor(anchor);
+            };
+
+            // offset of the anchor data just past the `{{#aa `, or
+            // however the mdbook include tag opened, which is found
+            // back from the closing `}}` as the data has no `}` in it
+            let close = source[offset..].find("}}").unwrap_or_default();
+            let data_offset = offset + close.saturating_sub(data.len());
+            let mut anchor = match Anchor::parse(&data) {
+                Ok(anchor) => anchor,
+                Err(DocError::ParseAnchor(err)) => Err(Diagnostic::at_offset(
+                    &path,
+                    &source,
+                    data_offset + err.offset,
+                    err.message,
+                ))?,
+                Err(err) => Err(err)?,
+            };
+            if rustdoc {
+                anchor.decoration = Stage::Hidden.into();
+            }
+            if anchor.link.path.is_relative() {
+                let root = path.parent().unwrap();
+                anchor.link.path = root.join(anchor.link.path);
             }
+            ast.push(Token::Anchor(anchor));
         }
         Ok(Self { path, source, ast })
     }
 
-    pub(super) fn parse_from_path(path: PathBuf) -> DocResult<Self> {
+    pub(super) fn parse_from_path(path: PathBuf, options: &ParseOptions) -> DocResult<Self> {
         let source = std::fs::read_to_string(&path)?;
-        Self::with_path_and_source(path, source)
+        Self::with_options(path, source, options)
     }
 }
 
@@ -58,7 +92,7 @@ mod test {
     #[test]
     fn parse_from_path_works() -> DocResult<()> {
         let doc_file = fixtures::sample_doc_filename();
-        let file = DocFile::parse_from_path(doc_file.clone())?;
+        let file = DocFile::parse_from_path(doc_file.clone(), &ParseOptions::default())?;
         assert_eq!(file.path, doc_file);
         assert_eq!(file.source, fixtures::sample_doc_contents());
         assert_eq!(
@@ -66,10 +100,12 @@ mod test {
             vec![
                 Token::Content("# Sample Doc\n\n```ruby\n".to_owned()),
                 Token::Anchor(Anchor {
-                    decoration: Decoration::None,
+                    decoration: Decoration::default(),
                     link: Link {
                         path: fixtures::sample_ruby_filename(),
                         strategy: Strategy::Full,
+                        language: None,
+                        revision: None,
                     }
                 }),
                 Token::Content("\n```\n".to_owned()),
@@ -77,4 +113,17 @@ mod test {
         );
         Ok(())
     }
+
+    #[test]
+    fn parse_errors_are_located() {
+        let source = "# Title\n\n```ruby\n{{#aa sample_ruby_file.rb:foo bar}}\n```\n";
+        let error = DocFile::with_path_and_source("chapter.md", source).unwrap_err();
+        let DocError::Located(diagnostic) = error else {
+            panic!("expected a located error, got {error:?}");
+        };
+        assert_eq!(diagnostic.path, PathBuf::from("chapter.md"));
+        assert_eq!(diagnostic.line, 4);
+        assert_eq!(diagnostic.column, 30);
+        assert_eq!(diagnostic.message, "unknown strategy after ':'");
+    }
 }
@@ -1,4 +1,4 @@
-use super::{DocFile, DocResult};
+use super::{DocFile, DocResult, ParseOptions};
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, RwLock};
@@ -12,6 +12,18 @@ type InnerList = Arc<RwLock<HashMap<PathBuf, SharedDoc>>>;
 pub struct DocList {
     root: PathBuf,
     files: InnerList,
+    options: ParseOptions,
+}
+
+/// An empty list rooted in the current working directory
+impl Default for DocList {
+    fn default() -> Self {
+        Self {
+            root: std::env::current_dir().unwrap_or_default(),
+            files: InnerList::default(),
+            options: ParseOptions::default(),
+        }
+    }
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -46,13 +58,24 @@ impl DocList {
         Ok(Self {
             root,
             files: InnerList::default(),
+            options: ParseOptions::default(),
         })
     }
 
+    /// rules applied to every file parsed into the list
+    pub fn with_options(mut self, options: ParseOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn options(&self) -> &ParseOptions {
+        &self.options
+    }
+
     /// Attempts to read the file path, parse it
     /// and fold it into the list.  If the path
     /// is relative it will be expanded to absolute.
-    pub fn fetch<P>(&mut self, file_path: P) -> DocResult<SharedDoc>
+    pub fn fetch<P>(&self, file_path: P) -> DocResult<SharedDoc>
     where
         P: AsRef<Path>,
     {
@@ -67,7 +90,7 @@ impl DocList {
             return Ok(file);
         }
 
-        let file = SharedDoc::new(DocFile::parse_from_path(path.clone())?);
+        let file = SharedDoc::new(DocFile::parse_from_path(path.clone(), &self.options)?);
         self.files.write().unwrap().insert(path, file.clone());
         Ok(file)
     }
@@ -94,7 +117,7 @@ mod test {
         let full_path = fixtures::sample_doc_filename();
         let file_dir = full_path.parent().unwrap().to_owned();
         let filename = full_path.file_name().unwrap().to_owned();
-        let mut list = DocList::new(file_dir)?;
+        let list = DocList::new(file_dir)?;
         let doc = list.fetch(filename)?;
         assert_eq!(doc.source, fixtures::sample_doc_contents());
         Ok(())
@@ -1,4 +1,5 @@
 mod parser;
+mod verbatim;
 
 use super::Anchor;
 pub use parser::ParseError;
@@ -14,12 +15,37 @@ pub enum Token {
     /// raw contents of a `{{#aa ...}}` tag
     RawAnchor(String),
 
+    /// raw contents of an mdbook `{{#include ...}}` tag,
+    /// or `{{#rustdoc_include ...}}` when `rustdoc` is set
+    RawInclude { data: String, rustdoc: bool },
+
     /// fully parsed anchor, ready for action
     Anchor(Anchor),
 }
 
+/// Opt-in rules for which tags are treated as anchors
+/// and where `{{#aa ...}}` tags are left alone instead
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ParseOptions {
+    /// tags inside of inline code spans are left as is
+    pub verbatim_code_spans: bool,
+
+    /// tags inside of fenced code blocks which have
+    /// `verbatim` in their info string are left as is
+    pub verbatim_fences: bool,
+
+    /// mdbook's `{{#include ...}}` and `{{#rustdoc_include ...}}`
+    /// tags are linked just as `{{#aa ...}}` tags are
+    pub mdbook_includes: bool,
+}
+
 impl Token {
-    pub(super) fn parse_tokens(source: &str) -> Result<Vec<Self>, ParseError> {
-        parser::parse(source)
+    /// Parses the source into tokens with each paired with
+    /// the byte offset in the source where it begins
+    pub(super) fn parse_located(
+        source: &str,
+        options: &ParseOptions,
+    ) -> Result<Vec<(usize, Self)>, ParseError> {
+        parser::parse_located(source, options)
     }
 }
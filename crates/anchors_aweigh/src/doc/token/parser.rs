@@ -1,45 +1,165 @@
-use super::Token;
+use super::verbatim::{self, Segment};
+use super::{ParseOptions, Token};
 use nom::branch::alt;
-use nom::bytes::complete::{is_not, tag, take_until};
-use nom::combinator::{eof, rest};
-use nom::error::Error;
-use nom::multi::many_till;
-use nom::sequence::delimited;
-use nom::{Finish, IResult, Parser};
+use nom::bytes::complete::{is_not, tag, take};
+use nom::character::complete::{multispace0, multispace1};
+use nom::combinator::{recognize, value};
+use nom::sequence::{delimited, pair, preceded, terminated};
+use nom::{Finish, IResult, Offset, Parser};
+
+const ANCHOR_OPEN: &str = "{{#aa ";
 
 #[derive(Debug, thiserror::Error)]
-#[error("{0:?}")]
-pub struct ParseError(#[from] Error<String>);
+#[error("{message} at offset {offset}")]
+pub struct ParseError {
+    /// byte offset into the document where parsing failed
+    pub offset: usize,
+
+    /// human explanation of what went wrong
+    pub message: String,
+}
+
+#[cfg(test)]
+fn parse(source: &str, options: &ParseOptions) -> Result<Vec<Token>, ParseError> {
+    let tokens = parse_located(source, options)?;
+    Ok(tokens.into_iter().map(|(_, token)| token).collect())
+}
+
+/// Tokens paired with the byte offset in the source where
+/// each one starts, the `{{` for anchors
+pub(super) fn parse_located(
+    source: &str,
+    options: &ParseOptions,
+) -> Result<Vec<(usize, Token)>, ParseError> {
+    let mut tokens: Vec<(usize, Token)> = Vec::new();
+
+    for segment in verbatim::segments(source, options) {
+        let parts = match segment {
+            Segment::Verbatim(text) => {
+                vec![(source.offset(text), Token::Content(String::from(text)))]
+            }
+            Segment::Active(text) => located_parts(source, text, options)?,
+        };
+
+        // escapes and verbatim segments leave neighboring
+        // content tokens which are folded back together
+        for part in parts {
+            match (tokens.last_mut(), part) {
+                (Some((_, Token::Content(last))), (_, Token::Content(text))) => {
+                    last.push_str(&text)
+                }
+                (_, part) => tokens.push(part),
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+fn located_parts<'a>(
+    source: &'a str,
+    text: &'a str,
+    options: &ParseOptions,
+) -> Result<Vec<(usize, Token)>, ParseError> {
+    let mut parts = Vec::new();
+    let mut input = text;
+
+    while !input.is_empty() {
+        let unclosed = || ParseError {
+            offset: source.offset(input),
+            message: match include_open(input) {
+                Ok((_, true)) => {
+                    "unclosed '{{#rustdoc_include' tag, expected '}}' on the same line"
+                }
+                Ok((_, false)) => "unclosed '{{#include' tag, expected '}}' on the same line",
+                Err(_) => "unclosed '{{#aa' tag, expected '}}' on the same line",
+            }
+            .to_owned(),
+        };
+        let (rem, part) = token(input, options).finish().map_err(|_| unclosed())?;
+        if rem.len() == input.len() {
+            return Err(unclosed());
+        }
+        parts.push((source.offset(input), part));
+        input = rem;
+    }
 
-pub(super) fn parse(source: &str) -> Result<Vec<Token>, ParseError> {
-    let (_, parts) = all_parts(source)
-        .finish()
-        .map_err(|err| ParseError(err.into()))?;
     Ok(parts)
 }
 
 fn raw_anchor(input: &str) -> IResult<&str, Token> {
-    delimited(tag("{{#aa "), is_not("}\n"), tag("}}"))
+    delimited(tag(ANCHOR_OPEN), is_not("}\n"), tag("}}"))
         .map(|data| Token::RawAnchor(String::from(data)))
         .parse(input)
 }
 
-fn content(input: &str) -> IResult<&str, Token> {
-    alt((take_until("{{#aa "), rest))
+/// `\{{#aa ` is taken as the literal text `{{#aa `
+fn escaped_anchor(input: &str) -> IResult<&str, Token> {
+    preceded(tag("\\"), recognize(tag(ANCHOR_OPEN)))
         .map(|data| Token::Content(String::from(data)))
         .parse(input)
 }
 
-fn token(input: &str) -> IResult<&str, Token> {
-    alt((raw_anchor, content)).parse(input)
+/// Opening of an mdbook `{{#include ` or `{{#rustdoc_include `
+/// tag, allowing the same whitespace mdbook does, and if it is
+/// the latter of the two
+fn include_open(input: &str) -> IResult<&str, bool> {
+    delimited(
+        pair(tag("{{"), multispace0),
+        alt((
+            value(true, tag("#rustdoc_include")),
+            value(false, tag("#include")),
+        )),
+        multispace1,
+    )
+    .parse(input)
 }
 
-fn all_parts(input: &str) -> IResult<&str, Vec<Token>> {
-    many_till(token, eof)
-        .map(|(parts, _)| parts) // Throw away eof empty slice
+fn raw_include(input: &str) -> IResult<&str, Token> {
+    (include_open, terminated(is_not("}\n"), tag("}}")))
+        .map(|(rustdoc, data)| Token::RawInclude {
+            data: String::from(data),
+            rustdoc,
+        })
         .parse(input)
 }
 
+/// `\{{#include ` is taken as the literal text `{{#include `
+fn escaped_include(input: &str) -> IResult<&str, Token> {
+    preceded(tag("\\"), recognize(include_open))
+        .map(|data| Token::Content(String::from(data)))
+        .parse(input)
+}
+
+/// if a tag which should be parsed starts the input
+fn opens_tag(input: &str, options: &ParseOptions) -> bool {
+    input.starts_with(ANCHOR_OPEN) || (options.mdbook_includes && include_open(input).is_ok())
+}
+
+fn content<'a>(input: &'a str, options: &ParseOptions) -> IResult<&'a str, Token> {
+    let next = input
+        .match_indices("{{")
+        .map(|(at, _)| at)
+        .find(|at| opens_tag(&input[*at..], options));
+    let length = match next {
+        Some(at) if input[..at].ends_with('\\') => at - 1,
+        Some(at) => at,
+        None => input.len(),
+    };
+    take(length)
+        .map(|data| Token::Content(String::from(data)))
+        .parse(input)
+}
+
+fn token<'a>(input: &'a str, options: &ParseOptions) -> IResult<&'a str, Token> {
+    if options.mdbook_includes
+        && let Ok(found) = alt((raw_include, escaped_include)).parse(input)
+    {
+        return Ok(found);
+    }
+    alt((raw_anchor, escaped_anchor, |input| content(input, options))).parse(input)
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -55,36 +175,35 @@ mod test {
 
     #[test]
     fn content_parse_simple() {
-        let (rem, content) = content("...").unwrap();
+        let (rem, content) = content("...", &ParseOptions::default()).unwrap();
         assert!(rem.is_empty());
         assert_eq!(content, Token::Content("...".to_owned()));
     }
 
     #[test]
     fn part_parse_simple() {
-        let (rem, item) = token("aaa{{#aa file.rs}}bbb").unwrap();
+        let (rem, item) = token("aaa{{#aa file.rs}}bbb", &ParseOptions::default()).unwrap();
         assert_eq!(rem, "{{#aa file.rs}}bbb");
         assert_eq!(item, Token::Content("aaa".to_owned()));
 
-        let (rem, item) = token(rem).unwrap();
+        let (rem, item) = token(rem, &ParseOptions::default()).unwrap();
         assert_eq!(rem, "bbb");
         assert_eq!(item, Token::RawAnchor("file.rs".to_owned()));
 
-        let (rem, item) = token(rem).unwrap();
+        let (rem, item) = token(rem, &ParseOptions::default()).unwrap();
         assert_eq!("", rem);
         assert_eq!(item, Token::Content("bbb".to_owned()));
     }
 
     #[test]
     fn parse_parts_simple() {
-        let (rem, parts) = all_parts("aaa{{#aa file.rs}}bbb").unwrap();
-        assert_eq!(rem, "");
+        let parts = parse_located("aaa{{#aa file.rs}}bbb", &ParseOptions::default()).unwrap();
         assert_eq!(
             parts,
             vec![
-                Token::Content("aaa".to_owned()),
-                Token::RawAnchor("file.rs".to_owned()),
-                Token::Content("bbb".to_owned()),
+                (0, Token::Content("aaa".to_owned())),
+                (3, Token::RawAnchor("file.rs".to_owned())),
+                (18, Token::Content("bbb".to_owned())),
             ]
         );
     }
@@ -92,22 +211,98 @@ mod test {
     #[test]
     fn parse_sample_doc() {
         let doc = fixtures::sample_doc_contents();
-        let (rem, parts) = all_parts(&doc).unwrap();
-        assert_eq!(rem, "");
+        let parts = parse_located(&doc, &ParseOptions::default()).unwrap();
         assert_eq!(
             parts,
             vec![
-                Token::Content("# Sample Doc\n\n```ruby\n".to_owned()),
-                Token::RawAnchor("sample_ruby_file.rb".to_owned()),
-                Token::Content("\n```\n".to_owned()),
+                (0, Token::Content("# Sample Doc\n\n```ruby\n".to_owned())),
+                (22, Token::RawAnchor("sample_ruby_file.rb".to_owned())),
+                (49, Token::Content("\n```\n".to_owned())),
             ]
         );
     }
 
+    #[test]
+    fn unclosed_anchors_are_located() {
+        let error = parse("a\n{{#aa file.rs\n}}", &ParseOptions::default()).unwrap_err();
+        assert_eq!(error.offset, 2);
+    }
+
+    #[test]
+    fn escaped_anchor_parse() {
+        let parts = parse("a \\{{#aa file.rs}} b", &ParseOptions::default()).unwrap();
+        assert_eq!(
+            parts,
+            vec![Token::Content("a {{#aa file.rs}} b".to_owned())]
+        );
+
+        let parts = parse("\\{{#aa a.rs}}{{#aa b.rs}}", &ParseOptions::default()).unwrap();
+        assert_eq!(
+            parts,
+            vec![
+                Token::Content("{{#aa a.rs}}".to_owned()),
+                Token::RawAnchor("b.rs".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn verbatim_parse() {
+        let options = ParseOptions {
+            verbatim_code_spans: true,
+            verbatim_fences: true,
+            ..Default::default()
+        };
+        let parts = parse("`{{#aa a.rs}}` {{#aa b.rs}}", &options).unwrap();
+        assert_eq!(
+            parts,
+            vec![
+                Token::Content("`{{#aa a.rs}}` ".to_owned()),
+                Token::RawAnchor("b.rs".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn mdbook_includes_parse() {
+        let source =
+            "a {{#include file.rs:2:5}} b {{ #rustdoc_include  file.rs:name}} \\{{#include c.rs}}";
+        let options = ParseOptions {
+            mdbook_includes: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            parse(source, &options).unwrap(),
+            vec![
+                Token::Content("a ".to_owned()),
+                Token::RawInclude {
+                    data: "file.rs:2:5".to_owned(),
+                    rustdoc: false,
+                },
+                Token::Content(" b ".to_owned()),
+                Token::RawInclude {
+                    data: "file.rs:name".to_owned(),
+                    rustdoc: true,
+                },
+                Token::Content(" {{#include c.rs}}".to_owned()),
+            ]
+        );
+
+        let untouched = parse(source, &ParseOptions::default()).unwrap();
+        assert_eq!(untouched, vec![Token::Content(source.to_owned())]);
+
+        let error = parse("a {{#include file.rs\n}}", &options).unwrap_err();
+        assert_eq!(error.offset, 2);
+        assert_eq!(
+            error.message,
+            "unclosed '{{#include' tag, expected '}}' on the same line"
+        );
+    }
+
     #[test]
     fn parse_parts_sample_doc() {
         let doc = fixtures::sample_doc_contents();
-        let parts = parse(&doc).unwrap();
+        let parts = parse(&doc, &ParseOptions::default()).unwrap();
         assert_eq!(
             parts,
             vec![
@@ -0,0 +1,252 @@
+//! Verbatim Regions
+//!
+//! Finds the parts of a markdown document where `{{#aa ...}}`
+//! tags should be left untouched, such as inline code spans
+//! and fenced code blocks marked with `verbatim`.
+//!
+
+use super::ParseOptions;
+use std::ops::Range;
+
+/// Chunk of a markdown document, either to be searched
+/// for anchors or to be left exactly as it was found
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) enum Segment<'a> {
+    Active(&'a str),
+    Verbatim(&'a str),
+}
+
+/// Splits the source into active and verbatim segments
+/// based on which rules are turned on in the options
+pub(super) fn segments<'a>(source: &'a str, options: &ParseOptions) -> Vec<Segment<'a>> {
+    let mut ranges: Vec<(bool, Range<usize>)> = Vec::new();
+
+    for block in blocks(source) {
+        match block {
+            Block::Fence { range, verbatim } => {
+                ranges.push((verbatim && options.verbatim_fences, range));
+            }
+            Block::Text(range) if options.verbatim_code_spans => {
+                let mut at = range.start;
+                for span in code_spans(&source[range.clone()]) {
+                    let span = (span.start + range.start)..(span.end + range.start);
+                    ranges.push((false, at..span.start));
+                    at = span.end;
+                    ranges.push((true, span));
+                }
+                ranges.push((false, at..range.end));
+            }
+            Block::Text(range) => ranges.push((false, range)),
+        }
+    }
+
+    let mut segments: Vec<(bool, Range<usize>)> = Vec::with_capacity(ranges.len());
+    for (verbatim, range) in ranges.into_iter().filter(|(_, range)| !range.is_empty()) {
+        match segments.last_mut() {
+            Some((last, last_range)) if *last == verbatim && last_range.end == range.start => {
+                last_range.end = range.end;
+            }
+            _ => segments.push((verbatim, range)),
+        }
+    }
+
+    segments
+        .into_iter()
+        .map(|(verbatim, range)| match verbatim {
+            true => Segment::Verbatim(&source[range]),
+            false => Segment::Active(&source[range]),
+        })
+        .collect()
+}
+
+enum Block {
+    Text(Range<usize>),
+    Fence { range: Range<usize>, verbatim: bool },
+}
+
+struct Fence {
+    marker: char,
+    length: usize,
+    verbatim: bool,
+    start: usize,
+}
+
+impl Fence {
+    fn open(line: &str, start: usize) -> Option<Self> {
+        let trimmed = line.trim_start_matches(' ');
+        if line.len() - trimmed.len() > 3 {
+            return None;
+        }
+        let marker = trimmed.chars().next().filter(|c| *c == '`' || *c == '~')?;
+        let length = trimmed.chars().take_while(|c| *c == marker).count();
+        if length < 3 {
+            return None;
+        }
+        let info = &trimmed[length..];
+        if marker == '`' && info.contains('`') {
+            return None;
+        }
+        let verbatim = info
+            .split(|c: char| c.is_whitespace() || c == ',')
+            .any(|word| word == "verbatim");
+        Some(Self {
+            marker,
+            length,
+            verbatim,
+            start,
+        })
+    }
+
+    fn is_closed_by(&self, line: &str) -> bool {
+        let trimmed = line.trim_start_matches(' ');
+        if line.len() - trimmed.len() > 3 {
+            return false;
+        }
+        let length = trimmed.chars().take_while(|c| *c == self.marker).count();
+        length >= self.length && trimmed[length..].trim().is_empty()
+    }
+}
+
+fn blocks(source: &str) -> Vec<Block> {
+    let mut blocks = Vec::new();
+    let mut text_start = 0;
+    let mut offset = 0;
+    let mut fence: Option<Fence> = None;
+
+    for line in source.split_inclusive('\n') {
+        let line_start = offset;
+        offset += line.len();
+
+        match &fence {
+            None => {
+                if let Some(open) = Fence::open(line, line_start) {
+                    blocks.push(Block::Text(text_start..line_start));
+                    fence = Some(open);
+                }
+            }
+            Some(open) if open.is_closed_by(line) => {
+                blocks.push(Block::Fence {
+                    range: open.start..offset,
+                    verbatim: open.verbatim,
+                });
+                text_start = offset;
+                fence = None;
+            }
+            Some(_) => (),
+        }
+    }
+
+    match fence {
+        Some(open) => blocks.push(Block::Fence {
+            range: open.start..source.len(),
+            verbatim: open.verbatim,
+        }),
+        None => blocks.push(Block::Text(text_start..source.len())),
+    }
+
+    blocks
+}
+
+/// byte ranges of every inline code span, including
+/// the backticks which open and close them
+fn code_spans(text: &str) -> Vec<Range<usize>> {
+    let bytes = text.as_bytes();
+    let run_length = |at: usize| bytes[at..].iter().take_while(|b| **b == b'`').count();
+    let mut spans = Vec::new();
+    let mut at = 0;
+
+    while at < bytes.len() {
+        if bytes[at] == b'\\' {
+            at += 2;
+            continue;
+        }
+        if bytes[at] != b'`' {
+            at += 1;
+            continue;
+        }
+
+        let length = run_length(at);
+        let mut search = at + length;
+        let mut close = None;
+        while search < bytes.len() {
+            if bytes[search] == b'`' {
+                let found = run_length(search);
+                if found == length {
+                    close = Some(search + found);
+                    break;
+                }
+                search += found;
+            } else {
+                search += 1;
+            }
+        }
+
+        match close {
+            Some(end) => {
+                spans.push(at..end);
+                at = end;
+            }
+            None => at += length,
+        }
+    }
+
+    spans
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const ALL: ParseOptions = ParseOptions {
+        verbatim_code_spans: true,
+        verbatim_fences: true,
+        mdbook_includes: false,
+    };
+
+    #[test]
+    fn nothing_is_verbatim_by_default() {
+        let source = "a `{{#aa x}}`\n```md verbatim\n{{#aa y}}\n```\n";
+        let segments = segments(source, &ParseOptions::default());
+        assert_eq!(segments, vec![Segment::Active(source)]);
+    }
+
+    #[test]
+    fn code_spans_are_verbatim() {
+        let source = "a `{{#aa x}}` b ``{{#aa `y`}}`` c";
+        assert_eq!(
+            segments(source, &ALL),
+            vec![
+                Segment::Active("a "),
+                Segment::Verbatim("`{{#aa x}}`"),
+                Segment::Active(" b "),
+                Segment::Verbatim("``{{#aa `y`}}``"),
+                Segment::Active(" c"),
+            ]
+        );
+    }
+
+    #[test]
+    fn only_marked_fences_are_verbatim() {
+        let source = "```ruby\n{{#aa x.rb}}\n```\n~~~markdown verbatim\n{{#aa y}}\n~~~\nend";
+        assert_eq!(
+            segments(source, &ALL),
+            vec![
+                Segment::Active("```ruby\n{{#aa x.rb}}\n```\n"),
+                Segment::Verbatim("~~~markdown verbatim\n{{#aa y}}\n~~~\n"),
+                Segment::Active("end"),
+            ]
+        );
+    }
+
+    #[test]
+    fn unclosed_fences_run_to_the_end() {
+        let source = "a\n```verbatim\n{{#aa y}}\n";
+        assert_eq!(
+            segments(source, &ALL),
+            vec![
+                Segment::Active("a\n"),
+                Segment::Verbatim("```verbatim\n{{#aa y}}\n"),
+            ]
+        );
+    }
+}
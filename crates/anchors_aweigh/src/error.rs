@@ -5,6 +5,9 @@ pub enum Error {
 
     #[error(transparent)]
     Source(#[from] crate::source::SourceError),
+
+    #[error(transparent)]
+    Link(#[from] crate::linker::LinkError),
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -5,52 +5,156 @@
 //!
 
 mod assembly;
+mod error;
 mod linkage;
+mod settings;
 mod template;
 
 pub use assembly::{Assembly, Node};
+pub use error::LinkError;
 pub use linkage::Linkage;
+pub use settings::{BodyElision, Settings};
 pub use template::{Template, TemplateError, TemplateList};
 
-use crate::doc::{DocFile, Token};
+use crate::doc::{DocFile, DocList, NamedAnchor, Strategy, Token};
 use crate::error::Result;
-use crate::source::{QueryList, SourceList};
+use crate::source::{File, QueryList, RangeCache, SourceList};
+use std::path::PathBuf;
 
 #[derive(Debug, Default)]
 pub struct Linker {
     pub sources: SourceList,
     pub queries: QueryList,
     pub templates: TemplateList,
+    pub settings: Settings,
+    pub docs: DocList,
+    /// when set, ranges found by queries are kept between runs
+    pub cache: Option<RangeCache>,
 }
 
 impl Linker {
     pub fn build_assembly(&self, doc: &DocFile) -> Result<Assembly> {
+        let root = std::fs::canonicalize(&doc.path).unwrap_or_else(|_| doc.path.clone());
+        self.assemble(doc, None, &mut vec![root])
+    }
+
+    /// builds the assembly for a document which is `chain`
+    /// deep in includes, the last path being the document,
+    /// links without a revision of their own reading from
+    /// the `revision` the document was read from
+    fn assemble(
+        &self,
+        doc: &DocFile,
+        revision: Option<&str>,
+        chain: &mut Vec<PathBuf>,
+    ) -> Result<Assembly> {
         let mut nodes = Vec::with_capacity(doc.ast.len());
         for token in doc.ast.iter() {
             nodes.push(match token {
                 Token::Content(text) => Node::Text(text.clone()),
                 Token::Anchor(anchor) => {
-                    let source = self.sources.fetch(anchor)?;
-                    let contents = anchor.link.strategy.find_content(&source, self);
+                    let language = match &anchor.link.language {
+                        Some(name) => {
+                            Some(self.sources.languages().named(name).ok_or_else(|| {
+                                LinkError::UnknownLanguage {
+                                    language: name.clone(),
+                                    path: anchor.link.path.clone(),
+                                }
+                            })?)
+                        }
+                        None => None,
+                    };
+                    let revision = anchor.link.revision.as_deref().or(revision);
+                    let source = self.sources.fetch_at(anchor, language, revision)?;
+                    let (mut contents, selected) =
+                        match anchor.link.strategy.find_selection(&source, self)? {
+                            Some(selection) => (Some(selection.contents), selection.ranges),
+                            None => (None, Vec::new()),
+                        };
+                    if !self.settings.keep_anchor_markers {
+                        contents = contents.map(|data| NamedAnchor::strip_markers(&data));
+                    }
+                    let mut nested = None;
+                    let mut errors = Vec::new();
+                    if self.settings.expands(&source.path)
+                        && let Some(data) = contents.take()
+                    {
+                        let assembly = self.expand(&source, &anchor.link.strategy, data, chain)?;
+                        let (data, problems) = assembly.compile_with_errors(self);
+                        contents = Some(data);
+                        errors = problems;
+                        nested = Some(assembly);
+                    }
                     Node::Link(Linkage {
                         source,
                         contents,
+                        selected,
+                        nested,
+                        errors,
                         strategy: anchor.link.strategy.clone(),
                         decoration: anchor.decoration.clone(),
                     })
                 }
                 Token::RawAnchor(text) => Node::Text(format!("{{#aa {text} }}")),
+                Token::RawInclude { data, rustdoc } => Node::Text(match rustdoc {
+                    true => format!("{{{{#rustdoc_include {data}}}}}"),
+                    false => format!("{{{{#include {data}}}}}"),
+                }),
             });
         }
         Ok(Assembly { nodes })
     }
+
+    /// Links any anchors found in the contents selected from
+    /// an included file, paths being relative to that file
+    fn expand(
+        &self,
+        source: &File,
+        strategy: &Strategy,
+        contents: String,
+        chain: &mut Vec<PathBuf>,
+    ) -> Result<Assembly> {
+        if chain.contains(&source.path) {
+            let mut chain = chain.clone();
+            chain.push(source.path.clone());
+            Err(LinkError::IncludeCycle(chain))?;
+        }
+
+        if chain.len() > self.settings.max_include_depth {
+            let mut chain = chain.clone();
+            chain.push(source.path.clone());
+            Err(LinkError::DepthExceeded {
+                limit: self.settings.max_include_depth,
+                chain,
+            })?;
+        }
+
+        let doc = match strategy {
+            Strategy::Full if source.revision.is_none() => self.docs.fetch(&source.path)?,
+            _ => DocFile::with_options(&source.path, contents, self.docs.options())?.into(),
+        };
+
+        chain.push(source.path.clone());
+        let assembly = self.assemble(&doc, source.revision.as_deref(), chain);
+        chain.pop();
+        assembly
+    }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::doc::DocList;
+    use crate::doc::{DocList, ParseOptions};
+    use crate::source::Language;
     use crate::support::fixtures;
+    use std::collections::BTreeSet;
+    use std::path::Path;
+
+    #[test]
+    fn linker_can_be_shared_between_threads() {
+        fn shareable<T: Send + Sync>() {}
+        shareable::<Linker>();
+    }
 
     #[test]
     fn it_works() {
@@ -58,11 +162,312 @@ mod test {
         let full_path = fixtures::sample_doc_filename();
         let file_dir = full_path.parent().unwrap().to_owned();
         let filename = full_path.file_name().unwrap().to_owned();
-        let mut list = DocList::new(file_dir).unwrap();
+        let list = DocList::new(file_dir).unwrap();
         let doc = list.fetch(filename).unwrap();
         let assembly = linker.build_assembly(&doc).unwrap();
         assert!(matches!(assembly.nodes[0], Node::Text(_)));
         assert!(matches!(assembly.nodes[1], Node::Link(_)));
         assert!(matches!(assembly.nodes[2], Node::Text(_)));
     }
+
+    #[test]
+    fn unresolved_links_are_reported() {
+        let linker = Linker::default();
+        let doc = DocFile::with_path_and_source(
+            fixtures::sample_doc_filename(),
+            "{{#aa sample_ruby_file.rb#class?name=Foo}}\n{{#aa sample_ruby_file.rb:2}}",
+        )
+        .unwrap();
+        let assembly = linker.build_assembly(&doc).unwrap();
+        let unresolved: Vec<_> = assembly.unresolved().collect();
+        assert_eq!(unresolved.len(), 1);
+        assert!(matches!(unresolved[0].strategy, Strategy::Query(_)));
+    }
+
+    #[test]
+    fn language_can_be_overridden() {
+        let linker = Linker::default();
+        let doc = DocFile::with_path_and_source(
+            fixtures::sample_doc_filename(),
+            "{{#aa sample_ruby_file.rb[lang=json]}}",
+        )
+        .unwrap();
+        let assembly = linker.build_assembly(&doc).unwrap();
+        let Node::Link(linkage) = &assembly.nodes[0] else {
+            panic!("expected a link");
+        };
+        assert_eq!(linkage.source.language, Some(Language::Json));
+
+        let doc = DocFile::with_path_and_source(
+            fixtures::sample_doc_filename(),
+            "{{#aa sample_ruby_file.rb[lang=cobol]}}",
+        )
+        .unwrap();
+        let error = linker.build_assembly(&doc).unwrap_err();
+        assert!(error.to_string().contains("unknown language [cobol]"));
+    }
+
+    #[test]
+    fn included_markdown_is_expanded() {
+        let linker = Linker::default();
+        let doc = DocList::default()
+            .fetch(fixtures::nested_doc_filename())
+            .unwrap();
+        let output = linker.build_assembly(&doc).unwrap().compile(&linker);
+        assert!(output.contains("# Sample Doc"));
+        assert!(output.contains("class Foo"));
+        assert!(!output.contains("{{#aa"));
+    }
+
+    #[test]
+    fn problems_within_includes_are_kept() {
+        let linker = Linker::default();
+        let doc = DocFile::with_path_and_source(
+            fixtures::sample_doc_filename(),
+            "{{#aa broken_include.md}}",
+        )
+        .unwrap();
+        let assembly = linker.build_assembly(&doc).unwrap();
+
+        let unresolved: Vec<_> = assembly.unresolved().collect();
+        assert_eq!(unresolved.len(), 1);
+        assert!(matches!(unresolved[0].strategy, Strategy::Query(_)));
+
+        let errors: Vec<_> = assembly.nested_errors().collect();
+        assert!(matches!(
+            errors[..],
+            [(include, TemplateError::TemplateMissing(_))] if include.ends_with("broken_include.md")
+        ));
+        assert!(assembly.compile_with_errors(&linker).1.is_empty());
+    }
+
+    #[test]
+    fn anchor_markers_are_stripped() {
+        let mut linker = Linker::default();
+        let path = fixtures::anchored_filename().with_extension("md");
+        let doc = DocFile::with_options(
+            path,
+            "{{#aa anchored_file.rs:outer}}\n{{#aa anchored_file.rs:2:4}}",
+            &Default::default(),
+        )
+        .unwrap();
+
+        let output = linker.build_assembly(&doc).unwrap().compile(&linker);
+        assert_eq!(
+            output,
+            "struct Outer {\n    inner: u8,\n}\nstruct Outer {\n    inner: u8,"
+        );
+
+        linker.settings.keep_anchor_markers = true;
+        let output = linker.build_assembly(&doc).unwrap().compile(&linker);
+        assert!(
+            output.contains("    // ANCHOR: inner\n    inner: u8,\n    // ANCHOR_END: inner\n")
+        );
+    }
+
+    #[test]
+    fn mdbook_includes_are_linked() {
+        let linker = Linker::default();
+        let path = fixtures::anchored_filename().with_extension("md");
+        let options = ParseOptions {
+            mdbook_includes: true,
+            ..Default::default()
+        };
+        let doc = DocFile::with_options(
+            path,
+            "{{#include anchored_file.rs:inner}}\n{{#rustdoc_include anchored_file.rs:inner}}",
+            &options,
+        )
+        .unwrap();
+
+        let output = linker.build_assembly(&doc).unwrap().compile(&linker);
+        assert_eq!(
+            output,
+            "    inner: u8,\n# struct Outer {\n    inner: u8,\n# }"
+        );
+    }
+
+    #[test]
+    fn decorations_are_applied_in_order() {
+        let mut linker = Linker::default();
+        linker
+            .templates
+            .create("fenced", "```{{source.language}}\n{{contents}}\n```")
+            .unwrap();
+        let path = fixtures::anchored_filename().with_extension("md");
+        let doc =
+            DocFile::with_path_and_source(path, "{{#aa (<|trim|fenced) anchored_file.rs:inner}}")
+                .unwrap();
+
+        let output = linker.build_assembly(&doc).unwrap().compile(&linker);
+        assert_eq!(output, "```Rust\ninner: u8,\n```");
+    }
+
+    #[test]
+    fn fences_are_tagged_with_the_language() {
+        let linker = Linker::default();
+        let path = fixtures::anchored_filename().with_extension("md");
+        let doc = DocFile::with_path_and_source(
+            path,
+            "{{#aa (<|fence,ignore) anchored_file.rs:inner}}\n{{#aa (fence) anchored_file.rs[lang=toml]:inner}}",
+        )
+        .unwrap();
+
+        let output = linker.build_assembly(&doc).unwrap().compile(&linker);
+        assert_eq!(
+            output,
+            "```rust,ignore\ninner: u8,\n```\n```toml\n    inner: u8,\n```"
+        );
+    }
+
+    #[test]
+    fn elided_bodies_keep_signatures() {
+        let mut linker = Linker::default();
+        linker
+            .settings
+            .body_elisions
+            .get_mut(&Language::Ruby)
+            .unwrap()
+            .placeholder = String::from("# ...");
+        let path = fixtures::sample_ruby_filename().with_extension("md");
+        let doc =
+            DocFile::with_path_and_source(path, "{{#aa (elide|<) sample_ruby_file.rb:10:18}}")
+                .unwrap();
+
+        let output = linker.build_assembly(&doc).unwrap().compile(&linker);
+        assert_eq!(
+            output,
+            "class Foo\n  # @return [String]\n  attr_reader :name\n\n  # @param name [String]\n  def initialize(name)\n    # ...\n  end\nend"
+        );
+    }
+
+    #[test]
+    fn elided_selections_need_not_parse_alone() {
+        let linker = Linker::default();
+        let path = fixtures::sample_ruby_filename().with_extension("md");
+        let doc =
+            DocFile::with_path_and_source(path, "{{#aa (elide|<) sample_ruby_file.rb:10:17}}")
+                .unwrap();
+
+        let output = linker.build_assembly(&doc).unwrap().compile(&linker);
+        assert_eq!(
+            output,
+            "class Foo\n  # @return [String]\n  attr_reader :name\n\n  # @param name [String]\n  def initialize(name)\n    ...\n  end"
+        );
+    }
+
+    #[test]
+    fn hidden_lines_follow_query_selections() {
+        let mut linker = Linker::default();
+        let query = Language::Ruby
+            .build_query(r#"(method name: (identifier) @name (#eq? @name "{{name}}")) @match"#)
+            .unwrap();
+        linker.queries.register("method", query);
+        linker
+            .settings
+            .hidden_prefixes
+            .insert(Language::Ruby, String::from("#~ "));
+
+        let path = fixtures::sample_ruby_filename().with_extension("md");
+        let doc = DocFile::with_path_and_source(
+            path,
+            "{{#aa (#) sample_ruby_file.rb#method[2]?name=initialize}}",
+        )
+        .unwrap();
+        let output = linker.build_assembly(&doc).unwrap().compile(&linker);
+        let visible: Vec<&str> = output
+            .lines()
+            .filter(|line| !line.starts_with("#~ "))
+            .collect();
+        assert_eq!(
+            visible.join("\n"),
+            fixtures::sample_ruby_file_contents()
+                .lines()
+                .skip(24)
+                .take(3)
+                .collect::<Vec<_>>()
+                .join("\n")
+        );
+        assert_eq!(
+            output.lines().count(),
+            fixtures::sample_ruby_file_contents().lines().count()
+        );
+    }
+
+    #[test]
+    fn dependencies_include_nested_links() {
+        let linker = Linker::default();
+        let doc = DocList::default()
+            .fetch(fixtures::nested_doc_filename())
+            .unwrap();
+        let dependencies = linker.build_assembly(&doc).unwrap().dependencies();
+        let expected: BTreeSet<PathBuf> = [
+            fixtures::sample_doc_filename(),
+            fixtures::sample_ruby_filename(),
+        ]
+        .iter()
+        .map(|path| std::fs::canonicalize(path).unwrap())
+        .collect();
+        assert_eq!(dependencies, expected);
+    }
+
+    #[test]
+    fn included_revisions_apply_to_nested_links()
+    -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let dir = std::env::temp_dir().join(format!("aa-nested-revision-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir)?;
+        let repo = git2::Repository::init(&dir)?;
+        std::fs::write(dir.join("guide.md"), "{{#aa lib.rb}}\n")?;
+        std::fs::write(dir.join("lib.rb"), "class Old\nend\n")?;
+        let mut index = repo.index()?;
+        index.add_path(Path::new("guide.md"))?;
+        index.add_path(Path::new("lib.rb"))?;
+        let tree = repo.find_tree(index.write_tree()?)?;
+        let signature = git2::Signature::now("test", "test@example.com")?;
+        let commit = repo.commit(Some("HEAD"), &signature, &signature, "v1", &tree, &[])?;
+        repo.tag_lightweight("v1", &repo.find_object(commit, None)?, false)?;
+        std::fs::write(dir.join("guide.md"), "Rewritten\n")?;
+        std::fs::write(dir.join("lib.rb"), "class New\nend\n")?;
+
+        let linker = Linker::default();
+        let doc = DocFile::with_path_and_source(dir.join("chapter.md"), "{{#aa guide.md@v1}}")?;
+        let assembly = linker.build_assembly(&doc)?;
+        assert_eq!(assembly.compile(&linker), "class Old\nend\n\n");
+
+        let dependencies = assembly.dependencies();
+        assert!(dependencies.contains(&repo.path().join("refs/tags/v1")));
+        assert!(!dependencies.iter().any(|path| path.ends_with("lib.rb")));
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn include_cycles_are_errors() {
+        let linker = Linker::default();
+        let doc = DocList::default()
+            .fetch(fixtures::cycle_doc_filename())
+            .unwrap();
+        let error = linker.build_assembly(&doc).unwrap_err();
+        assert!(matches!(
+            error,
+            crate::error::Error::Link(LinkError::IncludeCycle(ref chain)) if chain.len() == 3
+        ));
+        assert!(error.to_string().contains("cycle_doc.md -> "));
+    }
+
+    #[test]
+    fn include_depth_is_limited() {
+        let mut linker = Linker::default();
+        linker.settings.max_include_depth = 0;
+        let doc = DocList::default()
+            .fetch(fixtures::nested_doc_filename())
+            .unwrap();
+        let error = linker.build_assembly(&doc).unwrap_err();
+        assert!(matches!(
+            error,
+            crate::error::Error::Link(LinkError::DepthExceeded { limit: 0, .. })
+        ));
+    }
 }
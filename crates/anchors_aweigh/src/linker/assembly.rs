@@ -1,4 +1,6 @@
 use super::{Linkage, Linker, TemplateError};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
 
 #[derive(Debug)]
 pub enum Node {
@@ -34,15 +36,80 @@ pub struct Assembly {
 
 impl Assembly {
     pub fn compile(&self, linker: &Linker) -> String {
-        self.nodes.iter().fold(
+        let (string, errors) = self.compile_with_errors(linker);
+        for (include, error) in self.nested_errors() {
+            ::log::error!("[{}] in {include:?}: {error}", error.kind());
+        }
+        for error in errors {
+            ::log::error!("[{}] {error}", error.kind());
+        }
+        string
+    }
+
+    /// Compiles every node it can, handing back all of the
+    /// template errors encountered along the way
+    pub fn compile_with_errors(&self, linker: &Linker) -> (String, Vec<TemplateError>) {
+        let mut errors = Vec::new();
+        let string = self.nodes.iter().fold(
             String::with_capacity(self.estimated_string_size()),
             |mut string, node| {
                 if let Err(error) = node.compile(linker, &mut string) {
-                    ::log::error!("[TemplateError]{error}");
+                    errors.push(error);
                 }
                 string
             },
-        )
+        );
+        (string, errors)
+    }
+
+    /// Every link made, including those made from within
+    /// included documents, in the order they appear
+    pub fn links(&self) -> Vec<&Linkage> {
+        let mut links = Vec::new();
+        for node in self.nodes.iter() {
+            if let Node::Link(link) = node {
+                links.push(link);
+                if let Some(nested) = &link.nested {
+                    links.extend(nested.links());
+                }
+            }
+        }
+        links
+    }
+
+    /// links whose anchor did not select any content,
+    /// including those within included documents
+    pub fn unresolved(&self) -> impl Iterator<Item = &Linkage> {
+        self.links()
+            .into_iter()
+            .filter(|link| link.contents.is_none())
+    }
+
+    /// template errors hit rendering included documents, along with
+    /// the document each came from, those of this document are
+    /// found when it is compiled
+    pub fn nested_errors(&self) -> impl Iterator<Item = (&Path, &TemplateError)> {
+        self.links().into_iter().flat_map(|link| {
+            let include = link.source.path.as_path();
+            link.errors.iter().map(move |error| (include, error))
+        })
+    }
+
+    /// Every source file linked, including any linked from within
+    /// included documents, which is what the output depends on.
+    /// Files read from a git revision depend on the git files saying
+    /// where that revision points rather than the file on disk.
+    pub fn dependencies(&self) -> BTreeSet<PathBuf> {
+        let mut paths = BTreeSet::new();
+        for link in self.links() {
+            match link.source.revision {
+                None => {
+                    paths.insert(link.source.path.clone());
+                }
+                Some(_) => paths.extend(link.source.revision_refs.iter().cloned()),
+            }
+        }
+        paths
     }
 
     pub fn estimated_string_size(&self) -> usize {
@@ -0,0 +1,28 @@
+use std::path::PathBuf;
+
+#[derive(Debug, thiserror::Error)]
+pub enum LinkError {
+    #[error("include cycle detected: {}", display_chain(.0))]
+    IncludeCycle(Vec<PathBuf>),
+
+    #[error("include depth of {limit} exceeded: {}", display_chain(.chain))]
+    DepthExceeded { limit: usize, chain: Vec<PathBuf> },
+
+    #[error("{path:?}#{query}: {error}")]
+    Query {
+        path: PathBuf,
+        query: String,
+        error: Box<crate::source::QueryError>,
+    },
+
+    #[error("unknown language [{language}] for {path:?}")]
+    UnknownLanguage { language: String, path: PathBuf },
+}
+
+fn display_chain(chain: &[PathBuf]) -> String {
+    chain
+        .iter()
+        .map(|path| path.display().to_string())
+        .collect::<Vec<_>>()
+        .join(" -> ")
+}
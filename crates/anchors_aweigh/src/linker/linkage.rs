@@ -1,6 +1,6 @@
-use super::{Linker, TemplateError};
-use crate::doc::{Decoration, Strategy};
-use crate::source::SharedFile;
+use super::{Assembly, Linker, TemplateError};
+use crate::doc::{Decoration, Extract, NamedAnchor, Stage, Strategy};
+use crate::source::{SharedFile, SourceRange};
 use serde::Serialize;
 
 #[derive(Debug, Serialize)]
@@ -9,23 +9,403 @@ pub struct Linkage {
     pub strategy: Strategy,
     pub contents: Option<String>,
     pub decoration: Decoration,
+    /// ranges of the source the contents were taken from
+    #[serde(skip)]
+    pub selected: Vec<SourceRange>,
+    /// anchors linked from within the contents when they were expanded
+    #[serde(skip)]
+    pub nested: Option<Assembly>,
+    /// problems rendering the expanded contents, kept so they can be
+    /// reported along with those of the document which included them
+    #[serde(skip)]
+    pub errors: Vec<TemplateError>,
+}
+
+/// What a template in the middle of a decoration sees, the same
+/// as the [Linkage] but with the contents left by earlier stages
+#[derive(Debug, Serialize)]
+struct Staged<'a> {
+    source: &'a SharedFile,
+    strategy: &'a Strategy,
+    contents: &'a str,
+    decoration: &'a Decoration,
 }
 
 impl Linkage {
     pub fn compile(&self, linker: &Linker, buf: &mut String) -> Result<(), TemplateError> {
-        let data = self.contents.as_deref().unwrap_or("");
+        let mut data = self.contents.clone().unwrap_or_default();
+        for stage in self.decoration.stages() {
+            let mut out = String::with_capacity(data.len());
+            self.apply(stage, &data, linker, &mut out)?;
+            data = out;
+        }
+        buf.push_str(&data);
+        Ok(())
+    }
 
-        match &self.decoration {
-            Decoration::None => buf.push_str(data),
-            Decoration::LeftShift => left_shift::lines(data, buf),
-            Decoration::Template(tpl_name) => {
-                let data = linker.templates.render(tpl_name, self)?;
+    fn apply(
+        &self,
+        stage: &Stage,
+        data: &str,
+        linker: &Linker,
+        buf: &mut String,
+    ) -> Result<(), TemplateError> {
+        match stage {
+            Stage::LeftShift => left_shift::lines(data, buf),
+            Stage::Trim => buf.push_str(trim::lines(data)),
+            Stage::Elide => match self.elided(linker) {
+                Some(elided) => buf.push_str(&elided),
+                None => buf.push_str(data),
+            },
+            Stage::Fence(attributes) => {
+                let language = match &self.source.language {
+                    Some(language) => Some(language.name()),
+                    None => self.source.path.extension().and_then(|ext| ext.to_str()),
+                };
+                let info: Vec<&str> = language
+                    .into_iter()
+                    .chain(attributes.iter().map(String::as_str))
+                    .collect();
+                fence::lines(data, &info.join(","), buf);
+            }
+            Stage::Template(tpl_name) => {
+                let staged = Staged {
+                    source: &self.source,
+                    strategy: &self.strategy,
+                    contents: data,
+                    decoration: &self.decoration,
+                };
+                let data = linker.templates.render(tpl_name, &staged)?;
                 buf.push_str(data.as_ref());
             }
+            Stage::Hidden => {
+                let visible: Vec<_> = self
+                    .selected
+                    .iter()
+                    .map(|range| range.start..=range.end)
+                    .collect();
+                if visible.is_empty() {
+                    buf.push_str(data);
+                } else {
+                    hidden::lines(
+                        &self.source.contents,
+                        &visible,
+                        linker.settings.hidden_prefix(self.source.language.as_ref()),
+                        linker.settings.keep_anchor_markers,
+                        buf,
+                    );
+                }
+            }
         };
 
         Ok(())
     }
+
+    /// The contents taken again from the source with every body
+    /// inside of the selection elided, `None` when the language has
+    /// no bodies to elide or the contents were expanded from includes
+    fn elided(&self, linker: &Linker) -> Option<String> {
+        let language = self.source.language.as_ref()?;
+        let elision = linker.settings.body_elisions.get(language)?;
+        if self.nested.is_some() || self.selected.is_empty() {
+            return None;
+        }
+        let tree = match self.source.tree() {
+            Ok(tree) => tree?,
+            Err(error) => {
+                ::log::warn!("bodies in {:?} were not elided: {error}", self.source.path);
+                return None;
+            }
+        };
+
+        let source = &self.source.contents;
+        let exact = matches!(
+            &self.strategy,
+            Strategy::Query(anchor) if anchor.extract == Extract::Exact
+        );
+        let pieces: Vec<String> = self
+            .selected
+            .iter()
+            .map(|range| {
+                let span = match exact {
+                    true => range.start_byte..range.end_byte,
+                    false => {
+                        let lines = SourceRange::from_lines(source, range.start..=range.end);
+                        lines.start_byte..lines.end_byte
+                    }
+                };
+                let mut buf = String::with_capacity(span.len());
+                elide::bodies(source, tree.root_node(), span, elision, &mut buf);
+                match exact {
+                    true => buf,
+                    false => buf.replace("\r\n", "\n"),
+                }
+            })
+            .collect();
+
+        let separator = match self.strategy {
+            Strategy::Query(_) => linker.settings.match_separator.as_str(),
+            _ => "",
+        };
+        let contents = pieces.join(separator);
+        Some(match linker.settings.keep_anchor_markers {
+            true => contents,
+            false => NamedAnchor::strip_markers(&contents),
+        })
+    }
+}
+
+mod elide {
+    use crate::linker::BodyElision;
+    use std::ops::Range;
+    use tree_sitter::Node;
+
+    /// The span of the input with every outermost body inside of
+    /// it that the elision names replaced by its placeholder, the
+    /// root being that of the tree parsed from the whole input.
+    /// Bodies holding named definitions of their own, such as a
+    /// class holding its methods, are left open so those inside
+    /// are elided instead.
+    pub fn bodies(
+        input: &str,
+        root: Node,
+        span: Range<usize>,
+        elision: &BodyElision,
+        buf: &mut String,
+    ) {
+        let mut ranges = vec![];
+        collect(root, &span, &elision.kinds, &mut ranges);
+
+        let mut last = span.start;
+        for range in ranges {
+            buf.push_str(&input[last..range.start]);
+            buf.push_str(&elision.placeholder);
+            last = range.end;
+        }
+        buf.push_str(&input[last..span.end]);
+    }
+
+    fn collect(node: Node, span: &Range<usize>, kinds: &[String], ranges: &mut Vec<Range<usize>>) {
+        let range = node.byte_range();
+        if range.end <= span.start || range.start >= span.end {
+            return;
+        }
+        let inside = span.start <= range.start && range.end <= span.end;
+        if inside && is_body(&node, kinds) && !holds_definitions(&node, kinds) {
+            ranges.push(range);
+            return;
+        }
+        for child in node.children(&mut node.walk()) {
+            collect(child, span, kinds, ranges);
+        }
+    }
+
+    /// whether the node is of a kind listed, alone or
+    /// as `parent > kind` when the parent must match too
+    fn is_body(node: &Node, kinds: &[String]) -> bool {
+        kinds.iter().any(|kind| match kind.split_once('>') {
+            Some((parent, kind)) => {
+                kind.trim() == node.kind()
+                    && node
+                        .parent()
+                        .is_some_and(|node| node.kind() == parent.trim())
+            }
+            None => kind == node.kind(),
+        })
+    }
+
+    /// whether any direct child, or any child of the body the
+    /// node wraps such as a block's statements, is a definition
+    fn holds_definitions(node: &Node, kinds: &[String]) -> bool {
+        let defines = |child: Node| {
+            child.child_by_field_name("name").is_some()
+                && child
+                    .child_by_field_name("body")
+                    .is_some_and(|body| is_body(&body, kinds))
+        };
+        node.named_children(&mut node.walk()).any(defines)
+            || node
+                .child_by_field_name("body")
+                .is_some_and(|body| body.named_children(&mut body.walk()).any(defines))
+    }
+
+    #[cfg(test)]
+    mod test {
+        use super::*;
+        use crate::source::Language;
+
+        fn elide(language: Language, input: &str, elision: &BodyElision) -> String {
+            let tree = language.parse(input).unwrap().unwrap();
+            let buf = &mut String::new();
+            bodies(input, tree.root_node(), 0..input.len(), elision, buf);
+            buf.to_owned()
+        }
+
+        #[test]
+        fn method_bodies_are_elided() {
+            let ruby = BodyElision::new(["body_statement"], "...");
+            let input =
+                "class Foo\n  # doc\n  def foo(a)\n    a.each do |x|\n      x\n    end\n  end\nend";
+            assert_eq!(
+                elide(Language::Ruby, input, &ruby),
+                "class Foo\n  # doc\n  def foo(a)\n    ...\n  end\nend"
+            );
+
+            let rust = BodyElision::new(["block"], "{ todo!() }");
+            let input = "/// doc\n#[inline]\npub fn foo() -> u8 {\n    for x in y {}\n}\nfn bar();";
+            assert_eq!(
+                elide(Language::Rust, input, &rust),
+                "/// doc\n#[inline]\npub fn foo() -> u8 { todo!() }\nfn bar();"
+            );
+        }
+
+        #[test]
+        fn only_bodies_in_the_span_are_elided() {
+            let rust = BodyElision::new(["block"], "{ ... }");
+            let input = "impl Foo {\n    fn a(&self) -> u8 {\n        1\n    }\n}\nfn b() {}";
+            let tree = Language::Rust.parse(input).unwrap().unwrap();
+            let start = input.find("    fn a").unwrap();
+            let end = input.find("\n}").unwrap();
+            let buf = &mut String::new();
+            bodies(input, tree.root_node(), start..end, &rust, buf);
+            assert_eq!(buf, "    fn a(&self) -> u8 { ... }");
+        }
+
+        #[test]
+        fn only_bodies_within_their_parent_are_elided() {
+            let rust = BodyElision::new(["function_item > block"], "{ ... }");
+            let input = "impl Foo {\n    const X: u8 = { 1 };\n    fn a(&self) -> u8 {\n        let f = || { 2 };\n        f()\n    }\n}";
+            assert_eq!(
+                elide(Language::Rust, input, &rust),
+                "impl Foo {\n    const X: u8 = { 1 };\n    fn a(&self) -> u8 { ... }\n}"
+            );
+        }
+
+        #[test]
+        fn blocks_holding_definitions_stay_open() {
+            let ruby = BodyElision::new(["body_statement", "do_block"], "...");
+            let input = "Class.new do\n  def a\n    1\n  end\nend\nlist.each do |x|\n  x\nend";
+            assert_eq!(
+                elide(Language::Ruby, input, &ruby),
+                "Class.new do\n  def a\n    ...\n  end\nend\nlist.each ..."
+            );
+        }
+    }
+}
+
+mod fence {
+    /// Wraps the input in a fenced code block, the fence being
+    /// longer than any run of backticks found in the input
+    pub fn lines(input: &str, info: &str, buf: &mut String) {
+        let longest = input
+            .split(|c| c != '`')
+            .map(str::len)
+            .max()
+            .unwrap_or_default();
+        let fence = "`".repeat(longest.max(2) + 1);
+
+        buf.push_str(&fence);
+        buf.push_str(info);
+        buf.push('\n');
+        buf.push_str(input);
+        if !input.is_empty() && !input.ends_with('\n') {
+            buf.push('\n');
+        }
+        buf.push_str(&fence);
+    }
+
+    #[cfg(test)]
+    mod test {
+        use super::*;
+
+        #[test]
+        fn fence_works() {
+            let buf = &mut String::new();
+            lines("fn main() {}", "rust,ignore", buf);
+            assert_eq!(buf, "```rust,ignore\nfn main() {}\n```");
+
+            let buf = &mut String::new();
+            lines("/// ```\n/// a ```` b\n", "rust", buf);
+            assert_eq!(buf, "`````rust\n/// ```\n/// a ```` b\n`````");
+        }
+    }
+}
+
+mod trim {
+    /// The input without any blank lines before or after it,
+    /// leaving the indentation of the first line alone
+    pub fn lines(input: &str) -> &str {
+        let start = input
+            .split_inclusive('\n')
+            .take_while(|line| line.trim().is_empty())
+            .map(str::len)
+            .sum::<usize>();
+        let input = &input[start..];
+        match input.trim_end().len() {
+            0 => "",
+            len => {
+                let end = input[len..].find('\n').map_or(input.len(), |at| len + at);
+                input[..end].trim_end_matches('\r')
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod test {
+        use super::*;
+
+        #[test]
+        fn trim_works() {
+            assert_eq!(lines("\n  \n  one\n\n  two  \n\n"), "  one\n\n  two  ");
+            assert_eq!(lines("one"), "one");
+            assert_eq!(lines(" \n \n"), "");
+        }
+    }
+}
+
+mod hidden {
+    use crate::doc::NamedAnchor;
+    use std::ops::RangeInclusive;
+
+    /// Every line of the source, hiding those outside of the
+    /// `visible` lines, counted from one, behind the `prefix`
+    pub fn lines(
+        source: &str,
+        visible: &[RangeInclusive<usize>],
+        prefix: &str,
+        markers: bool,
+        buf: &mut String,
+    ) {
+        let lines = source.lines().enumerate();
+        for (index, line) in lines.filter(|(_, line)| markers || !NamedAnchor::is_marker(line)) {
+            if !visible.iter().any(|lines| lines.contains(&(index + 1))) {
+                buf.push_str(prefix);
+            }
+            buf.push_str(line);
+            buf.push('\n');
+        }
+        buf.pop();
+    }
+
+    #[cfg(test)]
+    mod test {
+        use super::*;
+
+        #[test]
+        fn hidden_lines_work() {
+            let source = "use std::fmt;\n// ANCHOR: main\nfn main() {}\n// ANCHOR_END: main";
+            let buf = &mut String::new();
+            lines(source, &[3..=3], "# ", false, buf);
+            assert_eq!(buf, "# use std::fmt;\nfn main() {}");
+
+            let buf = &mut String::new();
+            lines(source, &[1..=1, 3..=3], "#! ", true, buf);
+            assert_eq!(
+                buf,
+                "use std::fmt;\n#! // ANCHOR: main\nfn main() {}\n#! // ANCHOR_END: main"
+            );
+        }
+    }
 }
 
 mod left_shift {
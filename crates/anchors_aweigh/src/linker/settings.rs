@@ -0,0 +1,103 @@
+use crate::source::Language;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Knobs which change how anchors are linked and
+/// rendered, shared by every document processed
+#[derive(Debug, Clone)]
+pub struct Settings {
+    /// placed between each of the matches when a
+    /// query anchor selects more than one of them
+    pub match_separator: String,
+
+    /// extensions of included files which are themselves
+    /// searched for `{{#aa ...}}` tags and expanded
+    pub expand_extensions: Vec<String>,
+
+    /// how deep included files may include other files
+    /// before linking gives up with an error
+    pub max_include_depth: usize,
+
+    /// leave `ANCHOR:` and `ANCHOR_END:` marker lines
+    /// in the selected content instead of removing them
+    pub keep_anchor_markers: bool,
+
+    /// what lines hidden by [Decoration::Hidden](crate::doc::Decoration)
+    /// are prefixed with by language, those not listed using `# `
+    pub hidden_prefixes: HashMap<Language, String>,
+
+    /// how [Stage::Elide](crate::doc::Stage) finds and replaces
+    /// bodies by language, those not listed are left alone
+    pub body_elisions: HashMap<Language, BodyElision>,
+}
+
+/// Which tree-sitter nodes hold the body of a definition in a
+/// language and the placeholder written in place of each one
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BodyElision {
+    /// node kinds holding a body, each either on its own such as
+    /// `block` or only within a parent such as `function_item > block`
+    pub kinds: Vec<String>,
+    pub placeholder: String,
+}
+
+impl BodyElision {
+    pub fn new<K, P>(kinds: K, placeholder: P) -> Self
+    where
+        K: IntoIterator,
+        K::Item: Into<String>,
+        P: Into<String>,
+    {
+        Self {
+            kinds: kinds.into_iter().map(Into::into).collect(),
+            placeholder: placeholder.into(),
+        }
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            match_separator: String::from("\n\n"),
+            expand_extensions: vec![String::from("md")],
+            max_include_depth: 10,
+            keep_anchor_markers: false,
+            hidden_prefixes: HashMap::new(),
+            body_elisions: HashMap::from([
+                (
+                    Language::Rust,
+                    BodyElision::new(["function_item > block"], "{ ... }"),
+                ),
+                (
+                    Language::Ruby,
+                    BodyElision::new(["body_statement", "do_block"], "..."),
+                ),
+                (
+                    Language::Javascript,
+                    BodyElision::new(["statement_block"], "{ ... }"),
+                ),
+            ]),
+        }
+    }
+}
+
+impl Settings {
+    /// prefix hiding a line of a file in this language
+    pub fn hidden_prefix(&self, language: Option<&Language>) -> &str {
+        language
+            .and_then(|language| self.hidden_prefixes.get(language))
+            .map_or("# ", String::as_str)
+    }
+
+    /// if the file at this path should have its own
+    /// anchors expanded when it is included
+    pub fn expands<P>(&self, path: P) -> bool
+    where
+        P: AsRef<Path>,
+    {
+        path.as_ref()
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| self.expand_extensions.iter().any(|allowed| allowed == ext))
+    }
+}
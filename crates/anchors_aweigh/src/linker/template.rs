@@ -1,8 +1,7 @@
 use handlebars::Handlebars;
+use serde::Serialize;
 use std::collections::HashMap;
 
-use crate::linker::Linkage;
-
 #[derive(Debug)]
 pub struct Template {
     handlebars: Handlebars<'static>,
@@ -28,6 +27,18 @@ pub enum TemplateError {
     TemplateMissing(String),
 }
 
+impl TemplateError {
+    /// name of the variant, used to label the error in reports
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Parse(_) => "Parse",
+            Self::Render(_) => "Render",
+            Self::NameTaken(_) => "NameTaken",
+            Self::TemplateMissing(_) => "TemplateMissing",
+        }
+    }
+}
+
 type Result<T> = ::std::result::Result<T, TemplateError>;
 
 impl Template {
@@ -43,18 +54,22 @@ impl Template {
         Ok(Self { handlebars })
     }
 
-    pub fn render(&self, linkage: &Linkage) -> Result<String> {
+    pub fn render<T>(&self, data: &T) -> Result<String>
+    where
+        T: Serialize,
+    {
         self.handlebars
-            .render("tpl", linkage)
+            .render("tpl", data)
             .map_err(TemplateError::Render)
     }
 
-    pub fn render_to<T>(&self, linkage: &Linkage, writer: T) -> Result<()>
+    pub fn render_to<T, W>(&self, data: &T, writer: W) -> Result<()>
     where
-        T: std::io::Write,
+        T: Serialize,
+        W: std::io::Write,
     {
         self.handlebars
-            .render_to_write("tpl", linkage, writer)
+            .render_to_write("tpl", data, writer)
             .map_err(TemplateError::Render)
     }
 }
@@ -72,27 +87,32 @@ impl TemplateList {
         Ok(())
     }
 
-    pub fn render(&self, name: &str, linkage: &Linkage) -> Result<String> {
+    pub fn render<T>(&self, name: &str, data: &T) -> Result<String>
+    where
+        T: Serialize,
+    {
         self.templates
             .get(name)
             .ok_or_else(|| TemplateError::TemplateMissing(name.to_owned()))?
-            .render(linkage)
+            .render(data)
     }
 
-    pub fn write_to<T>(&self, name: &str, linkage: &Linkage, writer: T) -> Result<()>
+    pub fn write_to<T, W>(&self, name: &str, data: &T, writer: W) -> Result<()>
     where
-        T: std::io::Write,
+        T: Serialize,
+        W: std::io::Write,
     {
         self.templates
             .get(name)
             .ok_or_else(|| TemplateError::TemplateMissing(name.to_owned()))?
-            .render_to(linkage, writer)
+            .render_to(data, writer)
     }
 }
 
 #[cfg(test)]
 mod test {
     use crate::doc::{Decoration, Strategy};
+    use crate::linker::Linkage;
     use crate::source::SourceList;
     use crate::support::fixtures;
 
@@ -106,7 +126,10 @@ mod test {
         let linkage = Linkage {
             strategy: Strategy::Full,
             contents: Some(source.contents.clone()),
-            decoration: Decoration::None,
+            decoration: Decoration::default(),
+            selected: Vec::new(),
+            nested: None,
+            errors: Vec::new(),
             source,
         };
         let template = Template::new("### Contents: ```ruby\n{{contents}}```").unwrap();
@@ -3,16 +3,25 @@
 //! Everything around reading files and parsing them
 //!
 
+mod cache;
 mod error;
 mod file;
+mod git;
+#[cfg(feature = "dynamic-grammars")]
+mod grammar;
 mod lang;
+mod language_map;
 mod list;
 mod query;
 mod range;
 
+pub use cache::RangeCache;
 pub use error::SourceError;
 pub use file::File;
+#[cfg(feature = "dynamic-grammars")]
+pub use grammar::{Grammar, GrammarError};
 pub use lang::Language;
+pub use language_map::LanguageMap;
 pub use list::{SharedFile, SourceList};
 pub use query::{Query, QueryError, QueryList};
 pub use range::SourceRange;
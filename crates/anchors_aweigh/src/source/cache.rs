@@ -0,0 +1,235 @@
+use super::{File, Query, QueryError, SourceRange};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+
+/// Version of how ranges are found and stored, part of every
+/// key so a change to either never reads an older entry.  Bump
+/// it along with any change to how a [SourceRange] is resolved.
+pub(crate) const CACHE_FORMAT: u32 = 1;
+
+/// # Range Cache
+///
+/// Keeps the ranges each query found on disk so that later runs
+/// can resolve the same anchor without parsing the source again.
+///
+/// Entries are keyed by the contents of the source along with the
+/// query, capture and bindings used, so editing any of them simply
+/// misses the cache.  Stale entries are left behind until they
+/// are [pruned](RangeCache::prune) or the cache is
+/// [cleared](RangeCache::clear).
+#[derive(Debug, Clone)]
+pub struct RangeCache {
+    dir: PathBuf,
+
+    /// entries read or written since the cache was created
+    used: Arc<Mutex<HashSet<PathBuf>>>,
+}
+
+/// unique suffix for files being written before they are renamed
+/// into place, so no reader ever sees a partly written entry
+static PENDING: AtomicUsize = AtomicUsize::new(0);
+
+impl RangeCache {
+    pub fn new<T>(dir: T) -> Self
+    where
+        T: Into<PathBuf>,
+    {
+        Self {
+            dir: dir.into(),
+            used: Arc::default(),
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Same as [Query::find_all] except the ranges are read from
+    /// the cache when this search has been made before, otherwise
+    /// they are found and written to the cache for next time
+    pub fn find_all<T>(
+        &self,
+        query: &Query,
+        source: &File,
+        capture: Option<&str>,
+        bindings: &T,
+    ) -> Result<Vec<SourceRange>, QueryError>
+    where
+        T: Serialize,
+    {
+        let entry = self.entry(query, source, capture, bindings);
+        self.used
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(entry.clone());
+        if let Some(ranges) = Self::read(&entry) {
+            ::log::trace!("cached ranges for {:?}", source.path);
+            return Ok(ranges);
+        }
+
+        let ranges = query.find_all(source, capture, bindings)?;
+        if let Err(error) = self.write(&entry, &ranges) {
+            ::log::warn!("could not cache ranges in {:?}: {error}", self.dir);
+        }
+        Ok(ranges)
+    }
+
+    /// Removes every entry which hasn't been read or written since
+    /// the cache was created, returning how many were removed.  Call
+    /// it once everything which uses the cache has been linked.
+    pub fn prune(&self) -> std::io::Result<usize> {
+        let entries = match std::fs::read_dir(&self.dir) {
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+            result => result?,
+        };
+        let used = self.used.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut removed = 0;
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "json") && !used.contains(&path) {
+                std::fs::remove_file(&path)?;
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+
+    /// removes the cache directory and everything in it
+    pub fn clear(&self) -> std::io::Result<()> {
+        match std::fs::remove_dir_all(&self.dir) {
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            result => result,
+        }
+    }
+
+    fn entry<T>(&self, query: &Query, source: &File, capture: Option<&str>, bindings: &T) -> PathBuf
+    where
+        T: Serialize,
+    {
+        let mut hasher = Sha256::new();
+        for part in [
+            env!("CARGO_PKG_VERSION"),
+            &CACHE_FORMAT.to_string(),
+            query.language().name(),
+            query.template(),
+            capture.unwrap_or_default(),
+            &handlebars::to_json(bindings).to_string(),
+            source.digest(),
+        ] {
+            hasher.update(part.len().to_le_bytes());
+            hasher.update(part.as_bytes());
+        }
+        let key: String = hasher
+            .finalize()
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect();
+        self.dir.join(format!("{key}.json"))
+    }
+
+    fn read(entry: &Path) -> Option<Vec<SourceRange>> {
+        let data = std::fs::read(entry).ok()?;
+        serde_json::from_slice(&data).ok()
+    }
+
+    fn write(&self, entry: &Path, ranges: &[SourceRange]) -> std::io::Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        let pending = entry.with_extension(format!(
+            "{}.{}.tmp",
+            std::process::id(),
+            PENDING.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::write(&pending, serde_json::to_vec(ranges)?)?;
+        std::fs::rename(&pending, entry)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::source::Language;
+    use crate::support::fixtures;
+    use serde_json::json;
+
+    fn cache(name: &str) -> RangeCache {
+        let dir = std::env::temp_dir().join(format!("aa-cache-{name}-{}", std::process::id()));
+        let cache = RangeCache::new(dir);
+        cache.clear().unwrap();
+        cache
+    }
+
+    #[test]
+    fn cached_ranges_skip_parsing() -> Result<(), QueryError> {
+        let cache = cache("hit");
+        let query = Query::new(
+            Language::Ruby,
+            r#"(class name: (constant) @name (#eq? @name "{{name}}")) @match"#,
+        )?;
+        let bindings = json!({"name": "Bar"});
+
+        let first = File::open(fixtures::sample_ruby_filename()).unwrap();
+        let found = cache.find_all(&query, &first, None, &bindings)?;
+        assert!(first.is_parsed());
+        assert_eq!(found.len(), 1);
+
+        let second = File::open(fixtures::sample_ruby_filename()).unwrap();
+        assert_eq!(cache.find_all(&query, &second, None, &bindings)?, found);
+        assert!(!second.is_parsed());
+
+        let other = cache.find_all(&query, &second, None, &json!({"name": "Foo"}))?;
+        assert_ne!(other, found);
+        assert!(second.is_parsed());
+
+        cache.clear().unwrap();
+        assert!(!cache.dir().exists());
+        Ok(())
+    }
+
+    #[test]
+    fn changed_contents_miss_the_cache() -> Result<(), QueryError> {
+        let cache = cache("miss");
+        let query = Query::new(Language::Ruby, "(class) @match")?;
+        let path = PathBuf::from("sample.rb");
+
+        let one = File::new(path.clone(), "class A\nend\n".into(), Some(Language::Ruby));
+        assert_eq!(cache.find_all(&query, &one, None, &json!({}))?.len(), 1);
+
+        let two = File::new(
+            path,
+            "class A\nend\nclass B\nend\n".into(),
+            Some(Language::Ruby),
+        );
+        assert_eq!(cache.find_all(&query, &two, None, &json!({}))?.len(), 2);
+
+        cache.clear().unwrap();
+        Ok(())
+    }
+
+    #[test]
+    fn pruning_keeps_only_used_entries() -> Result<(), QueryError> {
+        let cache = cache("prune");
+        let query = Query::new(Language::Ruby, "(class) @match")?;
+        let source = File::new(
+            PathBuf::from("sample.rb"),
+            "class A\nend\n".into(),
+            Some(Language::Ruby),
+        );
+        cache.find_all(&query, &source, None, &json!({}))?;
+        cache.find_all(&query, &source, None, &json!({"unused": true}))?;
+
+        let next = RangeCache::new(cache.dir());
+        next.find_all(&query, &source, None, &json!({}))?;
+        std::fs::create_dir_all(next.dir().join("grammars")).unwrap();
+        assert_eq!(next.prune().unwrap(), 1);
+        assert_eq!(next.prune().unwrap(), 0);
+        assert!(next.dir().join("grammars").exists());
+
+        next.clear().unwrap();
+        assert_eq!(next.prune().unwrap(), 0);
+        Ok(())
+    }
+}
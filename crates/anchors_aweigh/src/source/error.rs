@@ -1,8 +1,33 @@
+use std::path::PathBuf;
+
 #[derive(Debug, thiserror::Error)]
 pub enum SourceError {
+    #[error("could not read {path:?}")]
+    NotFound {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
     #[error(transparent)]
     Io(#[from] std::io::Error),
 
     #[error(transparent)]
     Language(#[from] tree_sitter::LanguageError),
+
+    #[error("invalid language pattern {pattern:?}")]
+    Pattern {
+        pattern: String,
+        source: globset::Error,
+    },
+
+    #[cfg(feature = "dynamic-grammars")]
+    #[error(transparent)]
+    Grammar(#[from] super::GrammarError),
+
+    #[error("could not read {path:?} at revision {revision}")]
+    Revision {
+        path: PathBuf,
+        revision: String,
+        source: git2::Error,
+    },
 }
@@ -1,6 +1,8 @@
-use super::{Language, SourceResult};
+use super::{Language, LanguageMap, SourceResult};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
 use tree_sitter::Tree;
 
 /// # Source File
@@ -12,34 +14,118 @@ pub struct File {
     pub path: PathBuf,
     pub contents: String,
     pub language: Option<Language>,
+    /// git revision the contents were read from, if not from disk
+    #[serde(default)]
+    pub revision: Option<String>,
+    /// git files which change when the revision moves, empty
+    /// when read from disk or from a revision which can't move
     #[serde(skip)]
-    pub tree: Option<Tree>,
+    pub revision_refs: Vec<PathBuf>,
+    /// parsed the first time it is asked for, see [File::tree]
+    #[serde(skip)]
+    tree: OnceLock<Option<Tree>>,
+    #[serde(skip)]
+    digest: OnceLock<String>,
 }
 
 impl File {
     pub fn open<T>(path: T) -> SourceResult<Self>
+    where
+        T: AsRef<Path>,
+    {
+        Self::open_with(path, &LanguageMap::default())
+    }
+
+    /// opens the file using `languages` to decide what it is written in
+    pub fn open_with<T>(path: T, languages: &LanguageMap) -> SourceResult<Self>
+    where
+        T: AsRef<Path>,
+    {
+        let path = std::fs::canonicalize(path)?;
+        let contents = std::fs::read_to_string(&path)?;
+        let language = languages.detect(&path, &contents);
+        Ok(Self::new(path, contents, language))
+    }
+
+    /// opens the file treating it as written in `language`
+    /// no matter what its name or contents suggest
+    pub fn open_as<T>(path: T, language: Language) -> SourceResult<Self>
     where
         T: AsRef<Path>,
     {
         let path = std::fs::canonicalize(path)?;
         let contents = std::fs::read_to_string(&path)?;
-        let language = Language::determine_from_path(&path);
-        let mut file = Self {
+        Ok(Self::new(path, contents, Some(language)))
+    }
+
+    /// Opens the file as it was at `revision` of the git repository
+    /// it is in, `language` taking priority over what `languages`
+    /// decides it is written in
+    pub fn open_at<T>(
+        path: T,
+        revision: &str,
+        language: Option<Language>,
+        languages: &LanguageMap,
+    ) -> SourceResult<Self>
+    where
+        T: AsRef<Path>,
+    {
+        let read = super::git::read(path.as_ref(), revision)?;
+        let language = language.or_else(|| languages.detect(&read.path, &read.contents));
+        Ok(Self {
+            revision: Some(revision.to_owned()),
+            revision_refs: read.refs,
+            ..Self::new(read.path, read.contents, language)
+        })
+    }
+
+    /// A file from contents already in hand, which
+    /// are not parsed until the tree is needed
+    pub fn new(path: PathBuf, contents: String, language: Option<Language>) -> Self {
+        Self {
             path,
             contents,
             language,
-            tree: None,
+            revision: None,
+            revision_refs: Vec::new(),
+            tree: OnceLock::new(),
+            digest: OnceLock::new(),
+        }
+    }
+
+    /// Syntax tree of the contents, parsing them the first time
+    /// it is asked for.  `None` when the language is not known.
+    pub fn tree(&self) -> SourceResult<Option<&Tree>> {
+        if let Some(tree) = self.tree.get() {
+            return Ok(tree.as_ref());
+        }
+        let tree = match &self.language {
+            Some(lang) => lang.parse(&self.contents)?,
+            None => None,
         };
-        file.recalculate_tree()?;
-        Ok(file)
+        Ok(self.tree.get_or_init(|| tree).as_ref())
+    }
+
+    /// whether the tree has been parsed yet
+    pub fn is_parsed(&self) -> bool {
+        self.tree.get().is_some()
     }
 
     pub fn recalculate_tree(&mut self) -> SourceResult<()> {
-        if let Some(lang) = self.language {
-            self.tree = lang.parse(&self.contents)?;
-        }
+        self.tree = OnceLock::new();
+        self.tree()?;
         Ok(())
     }
+
+    /// hex encoded sha256 of the contents
+    pub fn digest(&self) -> &str {
+        self.digest.get_or_init(|| {
+            Sha256::digest(self.contents.as_bytes())
+                .iter()
+                .map(|byte| format!("{byte:02x}"))
+                .collect()
+        })
+    }
 }
 
 #[cfg(test)]
@@ -53,4 +139,23 @@ mod test {
         assert_eq!(file.contents, fixtures::sample_ruby_file_contents());
         Ok(())
     }
+
+    #[test]
+    fn open_as_overrides_the_language() -> SourceResult<()> {
+        let file = File::open_as(fixtures::sample_ruby_filename(), Language::Markdown)?;
+        assert_eq!(file.language, Some(Language::Markdown));
+        Ok(())
+    }
+
+    #[test]
+    fn trees_are_parsed_when_needed() -> SourceResult<()> {
+        let file = File::open(fixtures::sample_ruby_filename())?;
+        assert!(!file.is_parsed());
+        assert!(file.tree()?.is_some());
+        assert!(file.is_parsed());
+
+        let text = File::new("notes".into(), "hello".into(), None);
+        assert!(text.tree()?.is_none());
+        Ok(())
+    }
 }
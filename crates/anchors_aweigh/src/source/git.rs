@@ -0,0 +1,100 @@
+use super::{SourceError, SourceResult};
+use git2::Repository;
+use std::path::{Component, Path, PathBuf};
+
+/// A file as it was at some revision of its repository
+pub(super) struct Revisioned {
+    /// the path resolved against the repository
+    pub path: PathBuf,
+    pub contents: String,
+    /// git files naming where the revision points, see [refs]
+    pub refs: Vec<PathBuf>,
+}
+
+/// Reads `path` as it was at `revision` of the git repository
+/// it is in.  The file need not exist on disk.
+pub(super) fn read(path: &Path, revision: &str) -> SourceResult<Revisioned> {
+    let path = resolve(path);
+    let failed = |source| SourceError::Revision {
+        path: path.clone(),
+        revision: revision.to_owned(),
+        source,
+    };
+
+    let start = path.parent().unwrap_or(&path);
+    let repo = Repository::discover(start).map_err(failed)?;
+    let workdir = repo
+        .workdir()
+        .map(resolve)
+        .ok_or_else(|| failed(git2::Error::from_str("repository has no working directory")))?;
+    let relative = path
+        .strip_prefix(&workdir)
+        .map_err(|_| failed(git2::Error::from_str("file is outside of the repository")))?;
+
+    let blob = repo
+        .revparse_single(revision)
+        .and_then(|object| object.peel_to_tree())
+        .and_then(|tree| tree.get_path(relative))
+        .and_then(|entry| entry.to_object(&repo))
+        .and_then(|object| object.peel_to_blob())
+        .map_err(failed)?;
+    let contents = String::from_utf8(blob.content().to_vec())
+        .map_err(|_| failed(git2::Error::from_str("file is not valid UTF-8")))?;
+    let refs = refs(&repo, revision);
+    Ok(Revisioned {
+        path,
+        contents,
+        refs,
+    })
+}
+
+/// Files in the git directory which change when `revision` moves
+/// to another commit, such as `.git/refs/heads/main` for `main`.
+/// A revision which doesn't go through a reference, such as a
+/// commit id, never moves and so has none.
+fn refs(repo: &Repository, revision: &str) -> Vec<PathBuf> {
+    let Ok((_, Some(reference))) = repo.revparse_ext(revision) else {
+        return Vec::new();
+    };
+    let common = repo.commondir();
+    let mut refs = vec![repo.path().join("HEAD"), common.join("packed-refs")];
+    let resolved = reference.resolve().ok();
+    for name in [reference.name(), resolved.as_ref().and_then(|r| r.name())] {
+        match name {
+            Some("HEAD") | None => {}
+            Some(name) => refs.push(common.join(name)),
+        }
+    }
+    refs.dedup();
+    refs
+}
+
+/// Absolute form of `path` with symlinks resolved for as much
+/// of it as exists, the rest being tidied up lexically
+fn resolve(path: &Path) -> PathBuf {
+    let mut base = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                base.pop();
+            }
+            other => base.push(other),
+        }
+    }
+
+    let mut rest = Vec::new();
+    let mut existing = base.as_path();
+    loop {
+        if let Ok(found) = std::fs::canonicalize(existing) {
+            return rest.iter().rev().fold(found, |path, part| path.join(part));
+        }
+        match (existing.parent(), existing.file_name()) {
+            (Some(parent), Some(name)) => {
+                rest.push(name.to_owned());
+                existing = parent;
+            }
+            _ => return base,
+        }
+    }
+}
@@ -0,0 +1,297 @@
+//! Grammars
+//!
+//! Tree-sitter grammars which are not compiled into the crate
+//! and are instead loaded while running, either from a compiled
+//! shared library or by compiling the grammar's C sources.
+//!
+
+use serde::{Serialize, Serializer};
+use sha2::{Digest, Sha256};
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use tree_sitter_language::LanguageFn;
+
+/// A tree-sitter grammar known by name along with the file
+/// extensions it should be used for.  Grammars are registered
+/// with a [LanguageMap](super::LanguageMap) and used through
+/// [Language::Loaded](super::Language::Loaded).
+#[derive(Debug)]
+pub struct Grammar {
+    name: String,
+    extensions: Vec<String>,
+    // dropped before the library it may point into
+    language: tree_sitter::Language,
+    _library: Option<libloading::Library>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum GrammarError {
+    #[error("grammar [{0}] is already registered")]
+    NameTaken(String),
+
+    #[error("loading grammar library {path:?}")]
+    Library {
+        path: PathBuf,
+        source: libloading::Error,
+    },
+
+    #[error("compiling grammar sources in {path:?}")]
+    Compile { path: PathBuf, source: cc::Error },
+
+    #[error("compiling grammar sources in {0:?}: compiler exited with failure")]
+    CompilerFailed(PathBuf),
+
+    #[error("grammar sources not found, expected {0:?}")]
+    MissingParser(PathBuf),
+
+    #[error("grammar scanner {0:?} is C++, only C scanners can be compiled")]
+    UnsupportedScanner(PathBuf),
+
+    #[error("grammar [{name}] has ABI version {version}, expected {min} through {max}")]
+    IncompatibleVersion {
+        name: String,
+        version: usize,
+        min: usize,
+        max: usize,
+    },
+
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+type GrammarResult<T> = Result<T, GrammarError>;
+
+impl Grammar {
+    /// Grammar for an already built tree-sitter language
+    pub fn new<N, E>(name: N, language: tree_sitter::Language, extensions: E) -> GrammarResult<Self>
+    where
+        N: Into<String>,
+        E: IntoIterator,
+        E::Item: Into<String>,
+    {
+        let name = name.into();
+        let version = language.abi_version();
+        let (min, max) = (
+            tree_sitter::MIN_COMPATIBLE_LANGUAGE_VERSION,
+            tree_sitter::LANGUAGE_VERSION,
+        );
+        if !(min..=max).contains(&version) {
+            return Err(GrammarError::IncompatibleVersion {
+                name,
+                version,
+                min,
+                max,
+            });
+        }
+
+        Ok(Self {
+            name,
+            extensions: extensions.into_iter().map(Into::into).collect(),
+            language,
+            _library: None,
+        })
+    }
+
+    /// Loads a compiled grammar from a shared library.  The
+    /// symbol defaults to `tree_sitter_<name>` which is what
+    /// the tree-sitter CLI generates for a grammar.
+    pub fn load_library<N, P, E>(
+        name: N,
+        path: P,
+        symbol: Option<&str>,
+        extensions: E,
+    ) -> GrammarResult<Self>
+    where
+        N: Into<String>,
+        P: AsRef<Path>,
+        E: IntoIterator,
+        E::Item: Into<String>,
+    {
+        let name = name.into();
+        let path = path.as_ref();
+        let symbol = symbol
+            .map(String::from)
+            .unwrap_or_else(|| format!("tree_sitter_{}", name.replace('-', "_")));
+        let library_error = |source| GrammarError::Library {
+            path: path.to_owned(),
+            source,
+        };
+
+        // SAFETY: the library is trusted configuration from the
+        // book and the symbol has the signature tree-sitter emits
+        // for every grammar.  The library is kept open alongside
+        // the language for as long as the grammar lives.
+        let (library, language) = unsafe {
+            let library = libloading::Library::new(path).map_err(library_error)?;
+            let constructor = *library
+                .get::<unsafe extern "C" fn() -> *const ()>(symbol.as_bytes())
+                .map_err(library_error)?;
+            let language = tree_sitter::Language::new(LanguageFn::from_raw(constructor));
+            (library, language)
+        };
+
+        Ok(Self {
+            _library: Some(library),
+            ..Self::new(name, language, extensions)?
+        })
+    }
+
+    /// Compiles a grammar's `src/parser.c` and optional
+    /// `src/scanner.c` into a shared library kept in `out_dir`
+    /// then loads it.  Libraries are named by the contents of
+    /// their sources so one is only built when those change.
+    pub fn compile_source<N, P, O, E>(
+        name: N,
+        dir: P,
+        out_dir: O,
+        extensions: E,
+    ) -> GrammarResult<Self>
+    where
+        N: Into<String>,
+        P: AsRef<Path>,
+        O: AsRef<Path>,
+        E: IntoIterator,
+        E::Item: Into<String>,
+    {
+        let name = name.into();
+        let src = dir.as_ref().join("src");
+        let parser = src.join("parser.c");
+        if !parser.is_file() {
+            return Err(GrammarError::MissingParser(parser));
+        }
+
+        let mut sources = vec![parser];
+        let scanner = src.join("scanner.c");
+        if scanner.is_file() {
+            sources.push(scanner);
+        }
+        let cpp_scanner = src.join("scanner.cc");
+        if cpp_scanner.is_file() {
+            return Err(GrammarError::UnsupportedScanner(cpp_scanner));
+        }
+
+        let mut hasher = Sha256::new();
+        for part in [name.as_bytes(), TARGET.as_bytes()] {
+            hasher.update(part.len().to_le_bytes());
+            hasher.update(part);
+        }
+        for source in sources.iter() {
+            let contents = std::fs::read(source)?;
+            hasher.update(contents.len().to_le_bytes());
+            hasher.update(contents);
+        }
+        let key: String = hasher
+            .finalize()
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect();
+
+        let out_dir = out_dir.as_ref();
+        std::fs::create_dir_all(out_dir)?;
+        let library = out_dir.join(libloading::library_filename(format!(
+            "tree-sitter-{name}-{key}"
+        )));
+
+        if !library.is_file() {
+            // built aside and renamed into place so a failed or
+            // concurrent build never leaves a partial library
+            let pending = out_dir.join(libloading::library_filename(format!(
+                "tree-sitter-{name}-{key}.{}.tmp",
+                std::process::id()
+            )));
+            compile(&src, &sources, &pending)?;
+            std::fs::rename(&pending, &library)?;
+        }
+
+        Self::load_library(name, library, None, extensions)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn extensions(&self) -> &[String] {
+        &self.extensions
+    }
+
+    pub fn language(&self) -> &tree_sitter::Language {
+        &self.language
+    }
+}
+
+/// target triple this crate was built for, captured by the build
+/// script as `cc` needs it when used outside of a cargo build
+const TARGET: &str = env!("ANCHORS_AWEIGH_TARGET");
+
+fn compile(src: &Path, sources: &[PathBuf], library: &Path) -> GrammarResult<()> {
+    let compiler = cc::Build::new()
+        .cargo_metadata(false)
+        .cargo_warnings(false)
+        .opt_level(2)
+        .warnings(false)
+        .target(TARGET)
+        .host(TARGET)
+        .include(src)
+        .try_get_compiler()
+        .map_err(|source| GrammarError::Compile {
+            path: src.to_owned(),
+            source,
+        })?;
+
+    let mut command = compiler.to_command();
+    if compiler.is_like_msvc() {
+        command.arg("/LD").arg(format!("/Fe{}", library.display()));
+    } else {
+        command.args(["-shared", "-fPIC", "-o"]).arg(library);
+    }
+    command.args(sources);
+
+    if !command.status()?.success() {
+        return Err(GrammarError::CompilerFailed(src.to_owned()));
+    }
+    Ok(())
+}
+
+impl PartialEq for Grammar {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
+
+impl Eq for Grammar {}
+
+impl Hash for Grammar {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.name.hash(state);
+    }
+}
+
+impl Serialize for Grammar {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&self.name)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn cpp_scanners_are_refused() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = std::env::temp_dir().join(format!("aa-cpp-scanner-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("src"))?;
+        std::fs::write(dir.join("src/parser.c"), "")?;
+        std::fs::write(dir.join("src/scanner.cc"), "")?;
+
+        let error = Grammar::compile_source("cpp-scanner", &dir, dir.join("out"), ["x"]);
+        std::fs::remove_dir_all(&dir)?;
+        assert!(matches!(
+            error,
+            Err(GrammarError::UnsupportedScanner(path)) if path.ends_with("src/scanner.cc")
+        ));
+        Ok(())
+    }
+}
@@ -1,9 +1,13 @@
+#[cfg(feature = "dynamic-grammars")]
+use super::Grammar;
 use super::{Query, QueryError, SourceResult};
 use serde::{Deserialize, Serialize};
 use std::path::Path;
+#[cfg(feature = "dynamic-grammars")]
+use std::sync::Arc;
 use tree_sitter::Tree;
 
-#[derive(Debug, Serialize, Deserialize, Copy, Clone, Hash, PartialEq, Eq)]
+#[derive(Debug, Serialize, Deserialize, Clone, Hash, PartialEq, Eq)]
 pub enum Language {
     Ruby,
     Rust,
@@ -12,6 +16,35 @@ pub enum Language {
     Elixir,
     Json,
     Markdown,
+    #[cfg(feature = "python")]
+    Python,
+    #[cfg(feature = "typescript")]
+    Typescript,
+    #[cfg(feature = "typescript")]
+    Tsx,
+    #[cfg(feature = "go")]
+    Go,
+    #[cfg(feature = "c")]
+    C,
+    #[cfg(feature = "cpp")]
+    Cpp,
+    #[cfg(feature = "java")]
+    Java,
+    #[cfg(feature = "bash")]
+    Bash,
+    #[cfg(feature = "yaml")]
+    Yaml,
+    #[cfg(feature = "html")]
+    Html,
+    #[cfg(feature = "css")]
+    Css,
+    #[cfg(feature = "sql")]
+    Sql,
+
+    /// grammar loaded while running, see [Grammar]
+    #[cfg(feature = "dynamic-grammars")]
+    #[serde(skip_deserializing)]
+    Loaded(Arc<Grammar>),
 }
 
 impl Language {
@@ -19,29 +52,148 @@ impl Language {
     where
         T: AsRef<Path>,
     {
-        let ext = path.as_ref().extension()?;
-        match ext.to_str()? {
-            "rb" => Some(Self::Ruby),
+        let path = path.as_ref();
+        Self::from_file_name(path.file_name()?.to_str()?)
+            .or_else(|| Self::from_extension(path.extension()?.to_str()?))
+    }
+
+    /// Language for well known files which have no
+    /// extension, or one which would be misleading
+    pub fn from_file_name(name: &str) -> Option<Self> {
+        match name {
+            "Gemfile" | "Rakefile" | "Guardfile" | "Vagrantfile" | "Brewfile" => Some(Self::Ruby),
+            "Cargo.lock" | "Pipfile" => Some(Self::Toml),
+            "mix.lock" => Some(Self::Elixir),
+            _ => None,
+        }
+    }
+
+    /// Language for a file extension, without the leading `.`
+    pub fn from_extension(ext: &str) -> Option<Self> {
+        match ext {
+            "rb" | "rake" | "gemspec" | "ru" => Some(Self::Ruby),
             "rs" => Some(Self::Rust),
             "toml" => Some(Self::Toml),
-            "js" => Some(Self::Javascript),
+            "js" | "mjs" | "cjs" | "jsx" => Some(Self::Javascript),
             "ex" | "exs" => Some(Self::Elixir),
-            "json" => Some(Language::Json),
-            "md" | "txt" => Some(Language::Markdown),
+            "json" | "jsonc" => Some(Self::Json),
+            "md" | "markdown" => Some(Self::Markdown),
+            #[cfg(feature = "python")]
+            "py" | "pyi" => Some(Self::Python),
+            #[cfg(feature = "typescript")]
+            "ts" | "mts" | "cts" => Some(Self::Typescript),
+            #[cfg(feature = "typescript")]
+            "tsx" => Some(Self::Tsx),
+            #[cfg(feature = "go")]
+            "go" => Some(Self::Go),
+            #[cfg(feature = "c")]
+            "c" | "h" => Some(Self::C),
+            #[cfg(feature = "cpp")]
+            "cpp" | "cc" | "cxx" | "hpp" | "hh" | "hxx" => Some(Self::Cpp),
+            #[cfg(feature = "java")]
+            "java" => Some(Self::Java),
+            #[cfg(feature = "bash")]
+            "sh" | "bash" => Some(Self::Bash),
+            #[cfg(feature = "yaml")]
+            "yaml" | "yml" => Some(Self::Yaml),
+            #[cfg(feature = "html")]
+            "html" | "htm" => Some(Self::Html),
+            #[cfg(feature = "css")]
+            "css" => Some(Self::Css),
+            #[cfg(feature = "sql")]
+            "sql" => Some(Self::Sql),
+            _ => None,
+        }
+    }
+
+    /// Language known by this name, such as those used
+    /// for the query tables in the book configuration
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "ruby" => Some(Self::Ruby),
+            "rust" => Some(Self::Rust),
+            "toml" => Some(Self::Toml),
+            "javascript" => Some(Self::Javascript),
+            "elixir" => Some(Self::Elixir),
+            "json" => Some(Self::Json),
+            "markdown" => Some(Self::Markdown),
+            #[cfg(feature = "python")]
+            "python" => Some(Self::Python),
+            #[cfg(feature = "typescript")]
+            "typescript" => Some(Self::Typescript),
+            #[cfg(feature = "typescript")]
+            "tsx" => Some(Self::Tsx),
+            #[cfg(feature = "go")]
+            "go" => Some(Self::Go),
+            #[cfg(feature = "c")]
+            "c" => Some(Self::C),
+            #[cfg(feature = "cpp")]
+            "cpp" => Some(Self::Cpp),
+            #[cfg(feature = "java")]
+            "java" => Some(Self::Java),
+            #[cfg(feature = "bash")]
+            "bash" => Some(Self::Bash),
+            #[cfg(feature = "yaml")]
+            "yaml" => Some(Self::Yaml),
+            #[cfg(feature = "html")]
+            "html" => Some(Self::Html),
+            #[cfg(feature = "css")]
+            "css" => Some(Self::Css),
+            #[cfg(feature = "sql")]
+            "sql" => Some(Self::Sql),
             _ => None,
         }
     }
 
+    /// name of the language as accepted by [Language::from_name]
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Ruby => "ruby",
+            Self::Rust => "rust",
+            Self::Toml => "toml",
+            Self::Javascript => "javascript",
+            Self::Elixir => "elixir",
+            Self::Json => "json",
+            Self::Markdown => "markdown",
+            #[cfg(feature = "python")]
+            Self::Python => "python",
+            #[cfg(feature = "typescript")]
+            Self::Typescript => "typescript",
+            #[cfg(feature = "typescript")]
+            Self::Tsx => "tsx",
+            #[cfg(feature = "go")]
+            Self::Go => "go",
+            #[cfg(feature = "c")]
+            Self::C => "c",
+            #[cfg(feature = "cpp")]
+            Self::Cpp => "cpp",
+            #[cfg(feature = "java")]
+            Self::Java => "java",
+            #[cfg(feature = "bash")]
+            Self::Bash => "bash",
+            #[cfg(feature = "yaml")]
+            Self::Yaml => "yaml",
+            #[cfg(feature = "html")]
+            Self::Html => "html",
+            #[cfg(feature = "css")]
+            Self::Css => "css",
+            #[cfg(feature = "sql")]
+            Self::Sql => "sql",
+            #[cfg(feature = "dynamic-grammars")]
+            Self::Loaded(grammar) => grammar.name(),
+        }
+    }
+
     pub fn build_query<T>(&self, template: T) -> Result<Query, QueryError>
     where
         T: AsRef<str>,
     {
-        Query::new(*self, template)
+        Query::new(self.clone(), template)
     }
 
     pub fn parse(&self, source: &str) -> SourceResult<Option<Tree>> {
         let mut parser = tree_sitter::Parser::new();
-        parser.set_language(&tree_sitter::Language::from(*self))?;
+        parser.set_language(&tree_sitter::Language::from(self.clone()))?;
         Ok(parser.parse(source, None))
     }
 }
@@ -56,6 +208,70 @@ impl From<Language> for tree_sitter::Language {
             Language::Elixir => tree_sitter_elixir::LANGUAGE.into(),
             Language::Json => tree_sitter_json::LANGUAGE.into(),
             Language::Markdown => tree_sitter_md::LANGUAGE.into(),
+            #[cfg(feature = "python")]
+            Language::Python => tree_sitter_python::LANGUAGE.into(),
+            #[cfg(feature = "typescript")]
+            Language::Typescript => tree_sitter_typescript::LANGUAGE_TYPESCRIPT.into(),
+            #[cfg(feature = "typescript")]
+            Language::Tsx => tree_sitter_typescript::LANGUAGE_TSX.into(),
+            #[cfg(feature = "go")]
+            Language::Go => tree_sitter_go::LANGUAGE.into(),
+            #[cfg(feature = "c")]
+            Language::C => tree_sitter_c::LANGUAGE.into(),
+            #[cfg(feature = "cpp")]
+            Language::Cpp => tree_sitter_cpp::LANGUAGE.into(),
+            #[cfg(feature = "java")]
+            Language::Java => tree_sitter_java::LANGUAGE.into(),
+            #[cfg(feature = "bash")]
+            Language::Bash => tree_sitter_bash::LANGUAGE.into(),
+            #[cfg(feature = "yaml")]
+            Language::Yaml => tree_sitter_yaml::LANGUAGE.into(),
+            #[cfg(feature = "html")]
+            Language::Html => tree_sitter_html::LANGUAGE.into(),
+            #[cfg(feature = "css")]
+            Language::Css => tree_sitter_css::LANGUAGE.into(),
+            #[cfg(feature = "sql")]
+            Language::Sql => tree_sitter_sequel::LANGUAGE.into(),
+            #[cfg(feature = "dynamic-grammars")]
+            Language::Loaded(grammar) => grammar.language().clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn names_round_trip() {
+        for lang in [Language::Ruby, Language::Rust, Language::Markdown] {
+            assert_eq!(Language::from_name(lang.name()), Some(lang));
+        }
+        assert_eq!(Language::from_name("cobol"), None);
+    }
+
+    #[test]
+    #[cfg(feature = "full")]
+    fn optional_grammars_parse() {
+        let samples = [
+            ("main.py", "def main():\n    pass\n"),
+            ("main.ts", "const x: number = 1;"),
+            ("main.tsx", "const x = <div />;"),
+            ("main.go", "package main"),
+            ("main.c", "int main() { return 0; }"),
+            ("main.cpp", "int main() { return 0; }"),
+            ("Main.java", "class Main {}"),
+            ("main.sh", "echo hello"),
+            ("main.yml", "key: value"),
+            ("index.html", "<p>hi</p>"),
+            ("main.css", "p { color: red; }"),
+            ("schema.sql", "SELECT 1;"),
+        ];
+        for (path, source) in samples {
+            let lang = Language::determine_from_path(path).unwrap();
+            assert_eq!(Language::from_name(lang.name()), Some(lang.clone()));
+            let tree = lang.parse(source).unwrap().unwrap();
+            assert!(!tree.root_node().has_error(), "{path} did not parse");
         }
     }
 }
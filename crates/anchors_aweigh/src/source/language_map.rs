@@ -0,0 +1,267 @@
+#[cfg(feature = "dynamic-grammars")]
+use super::{Grammar, GrammarError};
+use super::{Language, SourceError, SourceResult};
+use globset::{GlobBuilder, GlobMatcher};
+use std::collections::HashMap;
+use std::path::Path;
+#[cfg(feature = "dynamic-grammars")]
+use std::sync::Arc;
+
+/// number of lines from the top and bottom of a
+/// file which are searched for an editor modeline
+const MODELINE_SEARCH: usize = 5;
+
+/// # Language Map
+///
+/// Decides which [Language] a source file is written in.  Patterns
+/// registered here are tried first, then the built in file names and
+/// extensions known by [Language::determine_from_path], then those of
+/// any registered grammars, and lastly any modeline or shebang found
+/// in the contents.
+///
+#[derive(Debug, Default, Clone)]
+pub struct LanguageMap {
+    extensions: HashMap<String, Language>,
+    globs: Vec<(GlobMatcher, Language)>,
+    /// grammars loaded while running by their name
+    grammars: HashMap<String, Language>,
+    /// grammars loaded while running by their extensions
+    grammar_extensions: HashMap<String, Language>,
+}
+
+impl LanguageMap {
+    /// Built in language or registered grammar known by `name`
+    pub fn named(&self, name: &str) -> Option<Language> {
+        Language::from_name(name).or_else(|| self.grammars.get(name).cloned())
+    }
+
+    /// Registers a grammar loaded while running, known by its name
+    /// and used for its extensions when nothing built in claims them
+    #[cfg(feature = "dynamic-grammars")]
+    pub fn register(&mut self, grammar: Grammar) -> SourceResult<Language> {
+        if self.named(grammar.name()).is_some() {
+            Err(GrammarError::NameTaken(grammar.name().to_owned()))?;
+        }
+        let language = Language::Loaded(Arc::new(grammar));
+        let Language::Loaded(grammar) = &language else {
+            unreachable!()
+        };
+        for ext in grammar.extensions() {
+            self.grammar_extensions
+                .entry(ext.clone())
+                .or_insert_with(|| language.clone());
+        }
+        self.grammars
+            .insert(grammar.name().to_owned(), language.clone());
+        Ok(language)
+    }
+
+    /// Maps files matching `pattern` to `language`.  A pattern of
+    /// `.ext` matches the extension, anything else is a glob which
+    /// is matched against the file name, or against the end of the
+    /// path when the pattern contains a `/`.
+    pub fn insert(&mut self, pattern: &str, language: Language) -> SourceResult<()> {
+        if let Some(ext) = pattern.strip_prefix('.')
+            && !ext.contains(['.', '/', '*', '?', '[', '{'])
+        {
+            self.extensions.insert(ext.to_owned(), language);
+            return Ok(());
+        }
+
+        let glob = if pattern.starts_with('/') || pattern.starts_with("**") {
+            pattern.to_owned()
+        } else if pattern.contains('/') {
+            format!("**/{pattern}")
+        } else {
+            pattern.to_owned()
+        };
+        let matcher = GlobBuilder::new(&glob)
+            .literal_separator(true)
+            .build()
+            .map_err(|source| SourceError::Pattern {
+                pattern: pattern.to_owned(),
+                source,
+            })?
+            .compile_matcher();
+        self.globs.push((matcher, language));
+        Ok(())
+    }
+
+    /// determines the language of the file at `path`
+    /// which has already been read into `contents`
+    pub fn detect(&self, path: &Path, contents: &str) -> Option<Language> {
+        self.configured(path)
+            .or_else(|| Language::determine_from_path(path))
+            .or_else(|| {
+                self.grammar_extensions
+                    .get(path.extension()?.to_str()?)
+                    .cloned()
+            })
+            .or_else(|| modeline(self, contents))
+            .or_else(|| shebang(self, contents))
+    }
+
+    fn configured(&self, path: &Path) -> Option<Language> {
+        let name = path.file_name()?;
+        let globbed = self.globs.iter().find_map(|(glob, language)| {
+            let target = if glob.glob().glob().contains('/') {
+                path
+            } else {
+                Path::new(name)
+            };
+            glob.is_match(target).then(|| language.clone())
+        });
+        globbed.or_else(|| {
+            let ext = path.extension()?.to_str()?;
+            self.extensions.get(ext).cloned()
+        })
+    }
+}
+
+/// language named by a vim `ft=` or emacs `-*- mode: -*-`
+/// modeline near the top or bottom of the contents
+fn modeline(map: &LanguageMap, contents: &str) -> Option<Language> {
+    let lines: Vec<&str> = contents.lines().collect();
+    let tail = lines
+        .len()
+        .saturating_sub(MODELINE_SEARCH)
+        .max(MODELINE_SEARCH);
+    lines
+        .iter()
+        .take(MODELINE_SEARCH)
+        .chain(lines.iter().skip(tail))
+        .find_map(|line| vim_modeline(line).or_else(|| emacs_modeline(line)))
+        .and_then(|hint| language_hint(map, hint))
+}
+
+fn vim_modeline(line: &str) -> Option<&str> {
+    let start = ["vim:", "vi:", "ex:"].iter().find_map(|marker| {
+        let at = line.find(marker)?;
+        let bounded = line[..at].ends_with(char::is_whitespace) || at == 0;
+        bounded.then(|| at + marker.len())
+    })?;
+    line[start..]
+        .split([' ', '\t', ':'])
+        .find_map(|option| {
+            ["ft=", "filetype=", "syntax="]
+                .iter()
+                .find_map(|key| option.strip_prefix(key))
+        })
+        .filter(|value| !value.is_empty())
+}
+
+fn emacs_modeline(line: &str) -> Option<&str> {
+    let (_, rest) = line.split_once("-*-")?;
+    let (vars, _) = rest.split_once("-*-")?;
+    if !vars.contains(':') {
+        return Some(vars.trim());
+    }
+    vars.split(';').find_map(|var| {
+        let (key, value) = var.split_once(':')?;
+        (key.trim() == "mode").then(|| value.trim())
+    })
+}
+
+/// language of the interpreter named by a `#!` on the first line
+fn shebang(map: &LanguageMap, contents: &str) -> Option<Language> {
+    let line = contents.lines().next()?.strip_prefix("#!")?;
+    let mut words = line.split_whitespace();
+    let mut program = words.next()?.rsplit('/').next()?;
+    if program == "env" {
+        program = words.find(|word| !word.starts_with('-') && !word.contains('='))?;
+    }
+    let program = program.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+    match program {
+        "node" | "nodejs" => Some(Language::Javascript),
+        "jruby" | "truffleruby" => Some(Language::Ruby),
+        "sh" | "zsh" | "dash" | "ksh" => language_hint(map, "bash"),
+        program => language_hint(map, program),
+    }
+}
+
+/// language for a name or extension such as `ruby` or `rb`
+fn language_hint(map: &LanguageMap, hint: &str) -> Option<Language> {
+    let hint = hint.to_ascii_lowercase();
+    map.named(&hint)
+        .or_else(|| Language::from_extension(&hint))
+        .or_else(|| map.grammar_extensions.get(&hint).cloned())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn configured_patterns_come_first() -> SourceResult<()> {
+        let mut map = LanguageMap::default();
+        map.insert("*.txt", Language::Ruby)?;
+        map.insert(".conf", Language::Toml)?;
+        map.insert("config/*.json", Language::Javascript)?;
+
+        let detect = |path: &str| map.detect(Path::new(path), "");
+        assert_eq!(detect("/book/notes.txt"), Some(Language::Ruby));
+        assert_eq!(detect("/book/app.conf"), Some(Language::Toml));
+        assert_eq!(detect("/book/config/app.json"), Some(Language::Javascript));
+        assert_eq!(detect("/book/app.json"), Some(Language::Json));
+        assert_eq!(detect("/book/Gemfile"), Some(Language::Ruby));
+        assert_eq!(detect("/book/readme"), None);
+        assert!(map.insert("[oops", Language::Ruby).is_err());
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "dynamic-grammars")]
+    fn registered_grammars_are_found() -> SourceResult<()> {
+        let mut map = LanguageMap::default();
+        let grammar = Grammar::new("jsonx", tree_sitter_json::LANGUAGE.into(), ["jsonx", "rb"])?;
+        let lang = map.register(grammar)?;
+        assert_eq!(lang.name(), "jsonx");
+        assert_eq!(map.named("jsonx"), Some(lang.clone()));
+        assert_eq!(
+            map.detect(Path::new("/book/a.jsonx"), ""),
+            Some(lang.clone())
+        );
+        assert_eq!(
+            map.detect(Path::new("/book/a.rb"), ""),
+            Some(Language::Ruby)
+        );
+        assert_eq!(
+            map.detect(Path::new("/book/a"), "# vim: ft=jsonx"),
+            Some(lang.clone())
+        );
+        assert!(lang.parse("{\"a\": 1}")?.is_some());
+        assert!(LanguageMap::default().named("jsonx").is_none());
+
+        let taken = Grammar::new("jsonx", tree_sitter_json::LANGUAGE.into(), ["x"])?;
+        assert!(matches!(
+            map.register(taken),
+            Err(SourceError::Grammar(GrammarError::NameTaken(_)))
+        ));
+        let builtin = Grammar::new("ruby", tree_sitter_json::LANGUAGE.into(), ["x"])?;
+        assert!(map.register(builtin).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn contents_hint_the_language() {
+        let map = LanguageMap::default();
+        let detect = |contents: &str| map.detect(Path::new("/book/script"), contents);
+        assert_eq!(detect("#!/usr/bin/env ruby\nputs 1"), Some(Language::Ruby));
+        assert_eq!(
+            detect("#!/usr/bin/env -S node --harmony\n"),
+            Some(Language::Javascript)
+        );
+        assert_eq!(detect("#!/usr/local/bin/elixir\n"), Some(Language::Elixir));
+        assert_eq!(detect("# vim: set ft=ruby:\nputs 1"), Some(Language::Ruby));
+        assert_eq!(
+            detect("// -*- mode: rust -*-\nfn main() {}"),
+            Some(Language::Rust)
+        );
+        assert_eq!(
+            detect("a\nb\nc\nd\ne\nf\ng\n// vim: ft=js"),
+            Some(Language::Javascript)
+        );
+        assert_eq!(detect("#!/bin/unknown\n"), None);
+        assert_eq!(detect("the revim:ft=ruby"), None);
+    }
+}
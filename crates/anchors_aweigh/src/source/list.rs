@@ -1,29 +1,105 @@
-use super::{File, SourceResult};
+use super::{File, Language, LanguageMap, SourceError, SourceResult};
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
-use std::sync::{Arc, RwLock};
+use std::sync::{Arc, Mutex, RwLock};
 
 pub type SharedFile = Arc<File>;
-type InnerList = Arc<RwLock<HashMap<PathBuf, SharedFile>>>;
+/// a file which is parsed by whoever locks it first while
+/// anyone else after the same file waits for that to finish
+type Slot = Arc<Mutex<Option<SharedFile>>>;
+type Key = (PathBuf, Option<Language>, Option<String>);
+type InnerList = Arc<RwLock<HashMap<Key, Slot>>>;
 
 #[derive(Debug, Default)]
 pub struct SourceList {
     files: InnerList,
+    languages: LanguageMap,
+    revision: Option<String>,
 }
 
 impl SourceList {
+    /// decide the language of fetched files with `languages`
+    pub fn with_languages(mut self, languages: LanguageMap) -> Self {
+        self.languages = languages;
+        self
+    }
+
+    /// decides the language of fetched files, and knows
+    /// every language by name including loaded grammars
+    pub fn languages(&self) -> &LanguageMap {
+        &self.languages
+    }
+
+    /// read files from this git revision when the
+    /// link to them does not name a revision itself
+    pub fn with_revision(mut self, revision: Option<String>) -> Self {
+        self.revision = revision;
+        self
+    }
+
     pub fn fetch<T>(&self, path: T) -> SourceResult<SharedFile>
     where
         T: AsRef<Path>,
     {
-        let path = std::fs::canonicalize(path)?;
+        self.fetch_as(path, None)
+    }
+
+    /// Same as [SourceList::fetch] however when `language` is
+    /// given the file is parsed as that language instead
+    pub fn fetch_as<T>(&self, path: T, language: Option<Language>) -> SourceResult<SharedFile>
+    where
+        T: AsRef<Path>,
+    {
+        self.fetch_at(path, language, None)
+    }
 
-        if let Some(file) = self.files.read().unwrap().get(&path).cloned() {
-            return Ok(file);
+    /// Same as [SourceList::fetch_as] however the file is read as it
+    /// was at `revision`, or the list's own revision when not given
+    pub fn fetch_at<T>(
+        &self,
+        path: T,
+        language: Option<Language>,
+        revision: Option<&str>,
+    ) -> SourceResult<SharedFile>
+    where
+        T: AsRef<Path>,
+    {
+        let revision = revision.or(self.revision.as_deref());
+        let path = match revision {
+            // the file may only exist in the revision
+            Some(_) => path.as_ref().to_owned(),
+            None => std::fs::canonicalize(&path).map_err(|source| SourceError::NotFound {
+                path: path.as_ref().to_owned(),
+                source,
+            })?,
+        };
+
+        let key = (path, language.clone(), revision.map(String::from));
+        let found = self.files.read().unwrap().get(&key).cloned();
+        let slot = match found {
+            Some(slot) => slot,
+            None => self
+                .files
+                .write()
+                .unwrap()
+                .entry(key.clone())
+                .or_default()
+                .clone(),
+        };
+
+        let mut slot = slot.lock().unwrap();
+        if let Some(file) = slot.as_ref() {
+            return Ok(file.clone());
         }
 
-        let file = Arc::new(File::open(&path)?);
-        self.files.write().unwrap().insert(path, file.clone());
+        let file = Arc::new(match (revision, language) {
+            (Some(revision), language) => {
+                File::open_at(&key.0, revision, language, &self.languages)?
+            }
+            (None, Some(language)) => File::open_as(&key.0, language)?,
+            (None, None) => File::open_with(&key.0, &self.languages)?,
+        });
+        *slot = Some(file.clone());
         Ok(file)
     }
 }
@@ -46,4 +122,73 @@ mod test {
         assert_eq!(Arc::strong_count(&file), 3);
         Ok(())
     }
+
+    #[test]
+    fn fetch_at_reads_from_revisions() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = std::env::temp_dir().join(format!("aa-revision-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("src"))?;
+        let repo = git2::Repository::init(&dir)?;
+        let path = dir.join("src/lib.rb");
+
+        std::fs::write(
+            &path,
+            "class Old
+end
+",
+        )?;
+        let mut index = repo.index()?;
+        index.add_path(Path::new("src/lib.rb"))?;
+        let tree = repo.find_tree(index.write_tree()?)?;
+        let signature = git2::Signature::now("test", "test@example.com")?;
+        let commit = repo.commit(Some("HEAD"), &signature, &signature, "v1", &tree, &[])?;
+        repo.tag_lightweight("v1", &repo.find_object(commit, None)?, false)?;
+        std::fs::write(
+            &path,
+            "class New
+end
+",
+        )?;
+
+        let list = SourceList::default();
+        assert_eq!(list.fetch(&path)?.contents, "class New\nend\n");
+
+        let old = list.fetch_at(dir.join("src/../src/lib.rb"), None, Some("v1"))?;
+        assert_eq!(old.contents, "class Old\nend\n");
+        assert_eq!(old.language, Some(Language::Ruby));
+        assert_eq!(old.revision.as_deref(), Some("v1"));
+        assert!(
+            old.revision_refs
+                .contains(&repo.path().join("refs/tags/v1"))
+        );
+
+        let fixed = list.fetch_at(&path, None, Some(&commit.to_string()))?;
+        assert_eq!(fixed.contents, "class Old\nend\n");
+        assert!(fixed.revision_refs.is_empty());
+
+        let pinned = SourceList::default().with_revision(Some("v1".into()));
+        assert_eq!(pinned.fetch(&path)?.contents, "class Old\nend\n");
+
+        std::fs::remove_file(&path)?;
+        assert_eq!(pinned.fetch(&path)?.contents, "class Old\nend\n");
+        let missing = list.fetch_at(dir.join("src/gone.rb"), None, Some("v1"));
+        assert!(matches!(missing, Err(SourceError::Revision { .. })));
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn concurrent_fetches_parse_once() {
+        let list = SourceList::default();
+
+        let files: Vec<SharedFile> = std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..8)
+                .map(|_| scope.spawn(|| list.fetch(fixtures::sample_ruby_filename()).unwrap()))
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+
+        assert!(files.iter().all(|file| Arc::ptr_eq(file, &files[0])));
+    }
 }
@@ -1,18 +1,34 @@
 use super::{File, Language, SourceRange};
-use handlebars::Handlebars;
+use handlebars::{Handlebars, Template};
 use serde::Serialize;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
 use tree_sitter::Language as TSLanguage;
 use tree_sitter::Query as TreesitterQuery;
 use tree_sitter::{QueryCursor, StreamingIterator};
 
 mod list;
+mod predicate;
+mod standard;
+mod variables;
 pub use list::QueryList;
+pub use variables::Variables;
 
 #[derive(Debug)]
 pub struct Query {
     lang: Language,
     ts_lang: TSLanguage,
+    template: String,
     query: Handlebars<'static>,
+    variables: Variables,
+
+    /// template with its binding comparisons left for
+    /// [predicate::holds], when every binding is one
+    parameterized: Option<String>,
+
+    /// compiled queries by the source they were compiled from
+    compiled: RwLock<HashMap<String, Arc<TreesitterQuery>>>,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -31,46 +47,206 @@ pub enum QueryError {
 
     #[error("could not parse source")]
     NoSource,
+
+    #[error("query has no capture named @{0}")]
+    UnknownCapture(String),
+
+    #[error("query needs a value for `{0}`, such as `?{0}=...`")]
+    MissingBinding(String),
+
+    #[error("query has no binding named `{name}`, {}", expected_bindings(.expected))]
+    UnknownBinding { name: String, expected: Vec<String> },
+
+    #[error("no language named [{name}] for the queries in {dir:?}")]
+    UnknownLanguage {
+        name: String,
+        dir: std::path::PathBuf,
+    },
+
+    #[error("could not read {path:?}")]
+    Unreadable {
+        path: std::path::PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("{path:?}: {source}")]
+    InFile {
+        path: std::path::PathBuf,
+        source: Box<QueryError>,
+    },
 }
 
 type QueryResult<T> = Result<T, QueryError>;
 
+fn expected_bindings(expected: &[String]) -> String {
+    match expected {
+        [] => String::from("it takes none"),
+        names => format!("expected one of: {}", names.join(", ")),
+    }
+}
+
 impl Query {
+    /// Builds the query, checking that its template renders into a
+    /// valid tree-sitter query when filled with placeholder values
     pub fn new<T>(lang: Language, template: T) -> QueryResult<Self>
     where
         T: AsRef<str>,
     {
+        let query = Self::unchecked(lang, template)?;
+        let raw = query
+            .query
+            .render("query", &query.variables.placeholders())?;
+        TreesitterQuery::new(&query.ts_lang, &raw)?;
+        Ok(query)
+    }
+
+    /// builds the query without compiling it, only for
+    /// templates which are known to be valid already
+    pub(super) fn unchecked<T>(lang: Language, template: T) -> QueryResult<Self>
+    where
+        T: AsRef<str>,
+    {
+        let source = template.as_ref().to_owned();
+        let parameterized = predicate::parameterize(&source);
+        let template = Template::compile(&source)?;
+        let variables = Variables::from_template(&template);
         let mut query = Handlebars::new();
         query.register_escape_fn(handlebars::no_escape);
-        query.register_template_string("query", template)?;
+        query.register_template("query", template);
         Ok(Self {
-            ts_lang: lang.into(),
+            ts_lang: lang.clone().into(),
             lang,
+            template: source,
             query,
+            variables,
+            parameterized,
+            compiled: RwLock::default(),
         })
     }
 
+    /// The tree-sitter query for these bindings, compiled only the
+    /// first time the template renders into any particular source
+    fn compile<T>(&self, bindings: &T) -> QueryResult<Arc<TreesitterQuery>>
+    where
+        T: Serialize,
+    {
+        let raw = match &self.parameterized {
+            Some(raw) => Cow::Borrowed(raw.as_str()),
+            None => Cow::Owned(self.query.render("query", bindings)?),
+        };
+        if let Some(query) = self.compiled.read().unwrap().get(&*raw) {
+            return Ok(query.clone());
+        }
+        let query = Arc::new(TreesitterQuery::new(&self.ts_lang, &raw)?);
+        self.compiled
+            .write()
+            .unwrap()
+            .insert(raw.into_owned(), query.clone());
+        Ok(query)
+    }
+
+    /// the template the query was built from
+    pub fn template(&self) -> &str {
+        &self.template
+    }
+
+    /// bindings the query template refers to
+    pub fn variables(&self) -> &Variables {
+        &self.variables
+    }
+
+    /// Checks every required binding is given a value
+    /// and that no binding is given which is never used
+    pub fn check_bindings<'a, I>(&self, names: I) -> QueryResult<()>
+    where
+        I: IntoIterator<Item = &'a str>,
+    {
+        let names: Vec<&str> = names.into_iter().collect();
+        if let Some(unknown) = names.iter().find(|name| !self.variables.contains(name)) {
+            let all = self.variables.required.iter();
+            return Err(QueryError::UnknownBinding {
+                name: unknown.to_string(),
+                expected: all.chain(self.variables.optional.iter()).cloned().collect(),
+            });
+        }
+        match self
+            .variables
+            .required
+            .iter()
+            .find(|name| !names.contains(&name.as_str()))
+        {
+            Some(missing) => Err(QueryError::MissingBinding(missing.clone())),
+            None => Ok(()),
+        }
+    }
+
     pub fn language(&self) -> Language {
-        self.lang
+        self.lang.clone()
     }
 
     pub fn find<T>(&self, source: &File, bindings: &T) -> QueryResult<Option<SourceRange>>
     where
         T: Serialize,
     {
-        if source.tree.is_none() {
+        let Some(tree) = source.tree().map_err(|_| QueryError::NoSource)? else {
             return Ok(None);
-        }
+        };
 
-        let raw = self.query.render("query", bindings)?;
-        let query = TreesitterQuery::new(&self.ts_lang, &raw)?;
-        let tree = source.tree.as_ref().unwrap();
+        let query = self.compile(bindings)?;
+        let values = handlebars::to_json(bindings);
+        let text = source.contents.as_bytes();
         let mut cursor = QueryCursor::new();
 
         Ok(cursor
-            .matches(&query, tree.root_node(), source.contents.as_bytes())
-            .next()
-            .map(SourceRange::from))
+            .matches(&query, tree.root_node(), text)
+            .find(|found| predicate::holds(&query, found, &values, text))
+            .map(|found| SourceRange::from_match(found, &self.lang)))
+    }
+
+    /// Much like [Query::find] except every match found in
+    /// the source is returned in the order they were found.
+    /// When a capture name is provided only the nodes it
+    /// captured make up each range instead of the whole match.
+    pub fn find_all<T>(
+        &self,
+        source: &File,
+        capture: Option<&str>,
+        bindings: &T,
+    ) -> QueryResult<Vec<SourceRange>>
+    where
+        T: Serialize,
+    {
+        let Some(tree) = source.tree().map_err(|_| QueryError::NoSource)? else {
+            return Ok(Vec::new());
+        };
+
+        let query = self.compile(bindings)?;
+        let values = handlebars::to_json(bindings);
+        let text = source.contents.as_bytes();
+        let capture = capture
+            .map(|name| {
+                query
+                    .capture_index_for_name(name)
+                    .ok_or_else(|| QueryError::UnknownCapture(name.to_owned()))
+            })
+            .transpose()?;
+        let mut cursor = QueryCursor::new();
+        let mut matches = cursor.matches(&query, tree.root_node(), text);
+        let mut ranges = Vec::new();
+
+        while let Some(query_match) = matches.next() {
+            if !predicate::holds(&query, query_match, &values, text) {
+                continue;
+            }
+            match capture {
+                None => ranges.push(SourceRange::from_match(query_match, &self.lang)),
+                Some(index) => {
+                    ranges.extend(SourceRange::from_capture(query_match, index, &self.lang))
+                }
+            }
+        }
+
+        Ok(ranges)
     }
 }
 
@@ -96,14 +272,161 @@ mod test {
         )?;
 
         let opt = by_class.find(ruby_code, &json!({"name": "Foo"}))?;
-        assert!(matches!(opt, Some(SourceRange { start: 6, end: 18 })));
+        assert!(matches!(
+            opt,
+            Some(SourceRange {
+                start: 6,
+                end: 18,
+                ..
+            })
+        ));
 
         let opt = by_class.find(ruby_code, &json!({"name": "Bar"}))?;
-        assert!(matches!(opt, Some(SourceRange { start: 20, end: 28 })));
+        assert!(matches!(
+            opt,
+            Some(SourceRange {
+                start: 20,
+                end: 28,
+                ..
+            })
+        ));
 
         let opt = by_class.find(ruby_code, &json!({"name": "Rofl"}))?;
         assert!(opt.is_none());
 
         Ok(())
     }
+
+    #[test]
+    fn find_all_works() -> QueryResult<()> {
+        let ruby_code = &File::open(fixtures::sample_ruby_filename()).unwrap();
+        let initializers = Query::new(
+            Language::Ruby,
+            r#"(method name: (identifier) @name (#eq? @name "{{name}}")) @match"#,
+        )?;
+
+        let ranges = initializers.find_all(ruby_code, None, &json!({"name": "initialize"}))?;
+        assert!(matches!(
+            ranges.as_slice(),
+            [
+                SourceRange {
+                    start: 15,
+                    end: 17,
+                    ..
+                },
+                SourceRange {
+                    start: 25,
+                    end: 27,
+                    ..
+                }
+            ]
+        ));
+
+        let ranges = initializers.find_all(ruby_code, None, &json!({"name": "rofl"}))?;
+        assert!(ranges.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn find_all_by_capture_works() -> QueryResult<()> {
+        let ruby_code = &File::open(fixtures::sample_ruby_filename()).unwrap();
+        let initializers = Query::new(
+            Language::Ruby,
+            r#"(method name: (identifier) @name parameters: (_) @params (#eq? @name "{{name}}")) @match"#,
+        )?;
+        let bindings = json!({"name": "initialize"});
+
+        let ranges = initializers.find_all(ruby_code, Some("params"), &bindings)?;
+        assert_eq!(ranges.len(), 2);
+        assert_eq!(ranges[0].start, 15);
+        assert_eq!(ranges[0].end, 15);
+        assert_eq!(ranges[0].start_column, 18);
+        assert_eq!(ranges[0].fetch_bytes(ruby_code), "(name)");
+        assert_eq!(ranges[0].fetch_lines(ruby_code), "    def initialize(name)");
+
+        let error = initializers.find_all(ruby_code, Some("rofl"), &bindings);
+        assert!(matches!(error, Err(QueryError::UnknownCapture(_))));
+
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_queries_are_rejected() {
+        let typo = Query::new(
+            Language::Ruby,
+            r#"(method name: (identifier) @name (#eq? @nmae "{{name}}"))"#,
+        );
+        assert!(matches!(typo, Err(QueryError::Query(_))));
+
+        let node = Query::new(Language::Ruby, "(methd) @match");
+        assert!(matches!(node, Err(QueryError::Query(_))));
+
+        let template = Query::new(Language::Ruby, "(method) @match {{#if}}");
+        assert!(matches!(template, Err(QueryError::Template(_))));
+    }
+
+    #[test]
+    fn bindings_are_checked() -> QueryResult<()> {
+        let query = Query::new(
+            Language::Ruby,
+            r#"(method name: (identifier) @name (#eq? @name "{{name}}") {{#if params}}parameters: (_){{/if}}) @match"#,
+        )?;
+        query.check_bindings(["name"])?;
+        query.check_bindings(["name", "params"])?;
+
+        let missing = query.check_bindings(["params"]).unwrap_err();
+        assert!(matches!(missing, QueryError::MissingBinding(ref name) if name == "name"));
+        assert_eq!(
+            missing.to_string(),
+            "query needs a value for `name`, such as `?name=...`"
+        );
+
+        let unknown = query.check_bindings(["name", "nmae"]).unwrap_err();
+        assert_eq!(
+            unknown.to_string(),
+            "query has no binding named `nmae`, expected one of: name, params"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn compiled_queries_are_cached() -> QueryResult<()> {
+        let by_name = Query::new(
+            Language::Ruby,
+            r#"(class name: (constant) @name (#eq? @name "{{name}}")) @match"#,
+        )?;
+        let foo = by_name.compile(&json!({"name": "Foo"}))?;
+        let bar = by_name.compile(&json!({"name": "Bar"}))?;
+        assert!(Arc::ptr_eq(&foo, &bar));
+
+        let by_pattern = Query::new(
+            Language::Ruby,
+            r#"(class name: (constant) @name (#match? @name "{{name}}")) @match"#,
+        )?;
+        let foo = by_pattern.compile(&json!({"name": "Foo"}))?;
+        let bar = by_pattern.compile(&json!({"name": "Bar"}))?;
+        assert!(!Arc::ptr_eq(&foo, &bar));
+        assert!(Arc::ptr_eq(
+            &foo,
+            &by_pattern.compile(&json!({"name": "Foo"}))?
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn parameterized_queries_compare_bindings() -> QueryResult<()> {
+        let ruby_code = &File::open(fixtures::sample_ruby_filename()).unwrap();
+        let classes = Query::new(
+            Language::Ruby,
+            r#"(class name: (constant) @name (#not-eq? @name "{{skip}}")) @match"#,
+        )?;
+        let ranges = classes.find_all(ruby_code, None, &json!({"skip": "Foo"}))?;
+        assert_eq!(ranges.len(), 1);
+        assert_eq!(ranges[0].start, 20);
+
+        let ranges = classes.find_all(ruby_code, None, &json!({"skip": ["Foo", "Bar"]}))?;
+        assert!(ranges.is_empty());
+        Ok(())
+    }
 }
@@ -1,10 +1,11 @@
 //! Query List
 
 use std::collections::HashMap;
+use std::path::Path;
 
-use crate::source::Language;
+use crate::source::{Language, LanguageMap};
 
-use super::Query;
+use super::{Query, QueryError, standard};
 
 type LanguageQueries = HashMap<Language, NamedQueries>;
 type NamedQueries = HashMap<String, Query>;
@@ -15,8 +16,21 @@ pub struct QueryList {
 }
 
 impl QueryList {
-    pub fn fetch(&self, lang: Language, name: &str) -> Option<&Query> {
-        let queries = self.queries.get(&lang)?;
+    /// List with the queries bundled for every built in language,
+    /// such as `struct` and `fn` for Rust or `class` for Ruby
+    pub fn standard() -> Self {
+        let mut list = Self::default();
+        for (lang, name, template) in standard::queries() {
+            // each is compiled by the standard query tests
+            let query =
+                Query::unchecked(lang, template).expect("bundled query templates are valid");
+            list.register(name, query);
+        }
+        list
+    }
+
+    pub fn fetch(&self, lang: &Language, name: &str) -> Option<&Query> {
+        let queries = self.queries.get(lang)?;
         queries.get(name)
     }
 
@@ -29,4 +43,118 @@ impl QueryList {
             .or_default()
             .insert(name.into(), query);
     }
+
+    /// Registers every `<language>/<name>.scm` file found in `dir`,
+    /// the language being looked up by name in `languages`, and
+    /// replacing any query already registered by the same name.
+    /// Files which could not be loaded are skipped and their
+    /// errors returned, every other query is still registered.
+    pub fn load_dir<T>(&mut self, dir: T, languages: &LanguageMap) -> Vec<QueryError>
+    where
+        T: AsRef<Path>,
+    {
+        let dir = dir.as_ref();
+        let mut errors = Vec::new();
+
+        let entries = match read_dir_sorted(dir) {
+            Ok(entries) => entries,
+            Err(error) => return vec![error],
+        };
+
+        for lang_dir in entries.into_iter().filter(|path| path.is_dir()) {
+            let name = lang_dir.file_name().unwrap().to_string_lossy();
+            let Some(lang) = languages.named(&name) else {
+                errors.push(QueryError::UnknownLanguage {
+                    name: name.into_owned(),
+                    dir: lang_dir,
+                });
+                continue;
+            };
+
+            let files = match read_dir_sorted(&lang_dir) {
+                Ok(files) => files,
+                Err(error) => {
+                    errors.push(error);
+                    continue;
+                }
+            };
+
+            for path in files {
+                if path.extension().is_none_or(|ext| ext != "scm") || !path.is_file() {
+                    continue;
+                }
+                let query = std::fs::read_to_string(&path)
+                    .map_err(|source| QueryError::Unreadable {
+                        path: path.clone(),
+                        source,
+                    })
+                    .and_then(|template| {
+                        Query::new(lang.clone(), template).map_err(|source| QueryError::InFile {
+                            path: path.clone(),
+                            source: Box::new(source),
+                        })
+                    });
+                match query {
+                    Ok(query) => {
+                        let name = path.file_stem().unwrap().to_string_lossy();
+                        self.register(name, query);
+                    }
+                    Err(error) => errors.push(error),
+                }
+            }
+        }
+
+        errors
+    }
+}
+
+fn read_dir_sorted(dir: &Path) -> Result<Vec<std::path::PathBuf>, QueryError> {
+    let unreadable = |source| QueryError::Unreadable {
+        path: dir.to_owned(),
+        source,
+    };
+    let mut paths = std::fs::read_dir(dir)
+        .map_err(unreadable)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(unreadable)?;
+    paths.sort();
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::source::File;
+    use crate::support::fixtures;
+    use serde_json::json;
+
+    #[test]
+    fn load_dir_registers_scm_files() {
+        let mut list = QueryList::default();
+        let errors = list.load_dir(fixtures::queries_dirname(), &LanguageMap::default());
+        assert!(matches!(
+            errors.as_slice(),
+            [QueryError::UnknownLanguage { name, .. }] if name == "cobol"
+        ));
+        assert!(list.fetch(&Language::Ruby, "README").is_none());
+
+        let query = list.fetch(&Language::Ruby, "method").unwrap();
+        let source = File::open(fixtures::sample_ruby_filename()).unwrap();
+        let ranges = query
+            .find_all(&source, None, &json!({"name": "initialize"}))
+            .unwrap();
+        assert_eq!(ranges.len(), 2);
+        assert_eq!(ranges[0].start, 14);
+    }
+
+    #[test]
+    fn load_dir_reports_missing_dirs() {
+        let mut list = QueryList::default();
+        let errors = list.load_dir(
+            fixtures::queries_dirname().join("rofl"),
+            &LanguageMap::default(),
+        );
+        assert!(matches!(errors.as_slice(), [QueryError::Unreadable { .. }]));
+    }
 }
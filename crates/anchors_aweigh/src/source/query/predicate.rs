@@ -0,0 +1,137 @@
+//! Binding Predicates
+//!
+//! Most queries only use their bindings to compare a capture, such
+//! as `(#eq? @name "{{name}}")`.  Those comparisons are rewritten as
+//! `(#aa-eq? @name "name")` which tree-sitter leaves for us to check
+//! against the bindings, so the query compiles once no matter what
+//! it is bound to.
+
+use handlebars::JsonValue;
+use nom::branch::alt;
+use nom::bytes::complete::{tag, take_while1};
+use nom::character::complete::{char, multispace0, multispace1};
+use nom::sequence::{delimited, preceded, terminated};
+use nom::{IResult, Parser};
+use tree_sitter::{Query, QueryMatch, QueryPredicateArg};
+
+const EQ: &str = "aa-eq?";
+const NOT_EQ: &str = "aa-not-eq?";
+
+/// The template with every binding comparison rewritten,
+/// `None` when a binding is still used any other way
+pub(super) fn parameterize(template: &str) -> Option<String> {
+    let mut output = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(at) = rest.find('(') {
+        output.push_str(&rest[..at]);
+        rest = &rest[at..];
+        match comparison(rest) {
+            Ok((tail, (negated, capture, variable))) => {
+                let operator = if negated { NOT_EQ } else { EQ };
+                output.push_str(&format!("(#{operator} @{capture} \"{variable}\")"));
+                rest = tail;
+            }
+            Err(_) => {
+                output.push('(');
+                rest = &rest[1..];
+            }
+        }
+    }
+    output.push_str(rest);
+
+    (!output.contains("{{")).then_some(output)
+}
+
+/// Whether every binding comparison of the pattern which produced
+/// `found` holds, `bindings` being the serialized anchor bindings
+pub(super) fn holds(
+    query: &Query,
+    found: &QueryMatch,
+    bindings: &JsonValue,
+    source: &[u8],
+) -> bool {
+    query
+        .general_predicates(found.pattern_index)
+        .iter()
+        .all(|predicate| {
+            let negated = match &*predicate.operator {
+                EQ => false,
+                NOT_EQ => true,
+                _ => return true,
+            };
+            let [
+                QueryPredicateArg::Capture(index),
+                QueryPredicateArg::String(variable),
+            ] = &*predicate.args
+            else {
+                return true;
+            };
+            let expected = bindings.get(&**variable);
+            found.nodes_for_capture_index(*index).all(|node| {
+                let text = node.utf8_text(source).unwrap_or_default();
+                bound_to(expected, text) != negated
+            })
+        })
+}
+
+/// compares against every value when the binding is a list
+fn bound_to(expected: Option<&JsonValue>, text: &str) -> bool {
+    match expected {
+        Some(JsonValue::String(value)) => value == text,
+        Some(JsonValue::Array(values)) => values.iter().any(|value| value.as_str() == Some(text)),
+        _ => false,
+    }
+}
+
+/// `(#eq? @capture "{{variable}}")` giving whether it is
+/// negated, the capture name and the variable name
+fn comparison(input: &str) -> IResult<&str, (bool, &str, &str)> {
+    let operator = alt((tag("#eq?").map(|_| false), tag("#not-eq?").map(|_| true)));
+    let capture = preceded(char('@'), take_while1(is_capture_char));
+    let variable = delimited(
+        (tag("\"{{"), multispace0),
+        take_while1(is_variable_char),
+        (multispace0, tag("}}\"")),
+    );
+    delimited(
+        terminated(char('('), multispace0),
+        (
+            terminated(operator, multispace1),
+            terminated(capture, multispace1),
+            variable,
+        ),
+        preceded(multispace0, char(')')),
+    )
+    .parse(input)
+}
+
+fn is_capture_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '-' | '.')
+}
+
+fn is_variable_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn comparisons_are_rewritten() {
+        let template = r#"((class name: (constant) @name) @match (#eq? @name "{{ name }}") (#not-eq? @name "{{skip}}"))"#;
+        assert_eq!(
+            parameterize(template).unwrap(),
+            r#"((class name: (constant) @name) @match (#aa-eq? @name "name") (#aa-not-eq? @name "skip"))"#
+        );
+        assert_eq!(parameterize("(method) @match").unwrap(), "(method) @match");
+    }
+
+    #[test]
+    fn other_uses_are_left_alone() {
+        assert!(parameterize(r#"(method) @m (#match? @m "{{name}}")"#).is_none());
+        assert!(parameterize(r#"({{kind}}) @m"#).is_none());
+        assert!(parameterize(r#"(#any-of? @m {{#each names}}"{{this}}" {{/each}})"#).is_none());
+    }
+}
@@ -0,0 +1,253 @@
+//! Standard Queries
+//!
+//! Queries bundled for the built in languages, read from
+//! the `queries/<language>/<name>.scm` files of this crate.
+
+use crate::source::Language;
+
+macro_rules! bundled {
+    ($lang:expr, $dir:literal, [$($name:literal),* $(,)?]) => {
+        [$((
+            $lang,
+            $name,
+            include_str!(concat!("../../../queries/", $dir, "/", $name, ".scm")),
+        )),*]
+    };
+}
+
+/// every bundled query as its language, name and template
+pub(super) fn queries() -> Vec<(Language, &'static str, &'static str)> {
+    let mut queries = Vec::new();
+    queries.extend(bundled!(
+        Language::Rust,
+        "rust",
+        [
+            "const", "enum", "fn", "impl", "macro", "mod", "struct", "trait", "type", "union"
+        ]
+    ));
+    queries.extend(bundled!(
+        Language::Ruby,
+        "ruby",
+        ["class", "const", "method", "module"]
+    ));
+    queries.extend(bundled!(
+        Language::Javascript,
+        "javascript",
+        ["class", "fn", "method"]
+    ));
+    queries.extend(bundled!(Language::Elixir, "elixir", ["fn", "module"]));
+    queries.extend(bundled!(Language::Json, "json", ["key_val"]));
+    queries.extend(bundled!(Language::Toml, "toml", ["key_val", "table"]));
+    queries.extend(bundled!(Language::Markdown, "markdown", ["section"]));
+    #[cfg(feature = "python")]
+    queries.extend(bundled!(Language::Python, "python", ["class", "fn"]));
+    #[cfg(feature = "typescript")]
+    queries.extend(bundled!(
+        Language::Typescript,
+        "typescript",
+        ["class", "enum", "fn", "interface", "method", "type"]
+    ));
+    #[cfg(feature = "typescript")]
+    queries.extend(bundled!(
+        Language::Tsx,
+        "tsx",
+        ["class", "enum", "fn", "interface", "method", "type"]
+    ));
+    #[cfg(feature = "go")]
+    queries.extend(bundled!(
+        Language::Go,
+        "go",
+        ["fn", "interface", "method", "struct", "type"]
+    ));
+    #[cfg(feature = "c")]
+    queries.extend(bundled!(Language::C, "c", ["enum", "fn", "struct"]));
+    #[cfg(feature = "cpp")]
+    queries.extend(bundled!(
+        Language::Cpp,
+        "cpp",
+        ["class", "enum", "fn", "struct"]
+    ));
+    #[cfg(feature = "java")]
+    queries.extend(bundled!(
+        Language::Java,
+        "java",
+        ["class", "enum", "interface", "method"]
+    ));
+    #[cfg(feature = "bash")]
+    queries.extend(bundled!(Language::Bash, "bash", ["fn"]));
+    #[cfg(feature = "yaml")]
+    queries.extend(bundled!(Language::Yaml, "yaml", ["key_val"]));
+    #[cfg(feature = "html")]
+    queries.extend(bundled!(Language::Html, "html", ["id"]));
+    #[cfg(feature = "css")]
+    queries.extend(bundled!(Language::Css, "css", ["rule"]));
+    #[cfg(feature = "sql")]
+    queries.extend(bundled!(Language::Sql, "sql", ["table"]));
+    queries
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::source::{File, Query, QueryList};
+    use serde_json::json;
+    use std::path::PathBuf;
+
+    fn source(lang: Language, contents: &str) -> File {
+        File::new(PathBuf::from("sample"), contents.to_owned(), Some(lang))
+    }
+
+    /// lines selected by the single match of `name`
+    fn select(lang: Language, name: &str, bindings: serde_json::Value, contents: &str) -> String {
+        let list = QueryList::standard();
+        let query = list.fetch(&lang, name).unwrap();
+        query
+            .check_bindings(bindings.as_object().unwrap().keys().map(String::as_str))
+            .unwrap();
+        let file = source(lang.clone(), contents);
+        let ranges = query.find_all(&file, None, &bindings).unwrap();
+        assert_eq!(ranges.len(), 1, "{} {name} matched {ranges:?}", lang.name());
+        ranges[0].fetch_lines(&file)
+    }
+
+    #[test]
+    fn bundled_queries_compile() {
+        for (lang, name, template) in queries() {
+            let result = Query::new(lang.clone(), template);
+            assert!(result.is_ok(), "{}/{name}: {result:?}", lang.name());
+        }
+    }
+
+    #[test]
+    fn rust_items_include_docs_and_attributes() {
+        let code = "use std::fmt;\n\n/// a point\n#[derive(Debug)]\npub struct Point {\n    x: i32,\n}\n\nimpl<T> Wrap<T> {\n    fn new() {}\n}\n";
+        assert_eq!(
+            select(Language::Rust, "struct", json!({"name": "Point"}), code),
+            "/// a point\n#[derive(Debug)]\npub struct Point {\n    x: i32,\n}"
+        );
+        assert_eq!(
+            select(Language::Rust, "impl", json!({"name": "Wrap"}), code),
+            "impl<T> Wrap<T> {\n    fn new() {}\n}"
+        );
+        assert_eq!(
+            select(Language::Rust, "fn", json!({"name": "new"}), code),
+            "    fn new() {}"
+        );
+    }
+
+    #[test]
+    fn class_and_function_definitions() {
+        let js = "const a = 1;\n\n// shapes\nexport class Shape {}\nclass Other {}\n";
+        assert_eq!(
+            select(Language::Javascript, "class", json!({"name": "Shape"}), js),
+            "// shapes\nexport class Shape {}"
+        );
+
+        let ruby =
+            "class Foo\n  attr_reader :name\n\n  # says hi\n  def self.hi\n    :hi\n  end\nend\n";
+        assert_eq!(
+            select(Language::Ruby, "method", json!({"name": "hi"}), ruby),
+            "  # says hi\n  def self.hi\n    :hi\n  end"
+        );
+
+        let elixir = "defmodule Foo do\n  @doc \"adds\"\n  def add(a, b), do: a + b\nend\n";
+        assert_eq!(
+            select(Language::Elixir, "fn", json!({"name": "add"}), elixir),
+            "  @doc \"adds\"\n  def add(a, b), do: a + b"
+        );
+        assert_eq!(
+            select(Language::Elixir, "module", json!({"name": "Foo"}), elixir),
+            elixir.trim_end()
+        );
+    }
+
+    #[test]
+    fn data_keys_and_sections() {
+        let json = "{\n  \"name\": \"aa\",\n  \"version\": 1\n}";
+        assert_eq!(
+            select(Language::Json, "key_val", json!({"key": "version"}), json),
+            "  \"version\": 1"
+        );
+
+        let toml = "# deps\n[dependencies]\nlog = \"0.4\"\n\n[package]\nname = \"aa\"\n";
+        assert_eq!(
+            select(Language::Toml, "key_val", json!({"key": "log"}), toml),
+            "log = \"0.4\""
+        );
+        assert_eq!(
+            select(
+                Language::Toml,
+                "table",
+                json!({"name": "dependencies"}),
+                toml
+            ),
+            "# deps\n[dependencies]\nlog = \"0.4\"\n"
+        );
+
+        let markdown = "# Intro\n\nhello\n\n# Usage\n\nrun it\n";
+        assert_eq!(
+            select(
+                Language::Markdown,
+                "section",
+                json!({"name": "Intro"}),
+                markdown
+            ),
+            "# Intro\n\nhello\n"
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "full")]
+    fn optional_language_definitions() {
+        let c = "int x;\n\n/* a point */\nstruct Point {\n  int x;\n};\n";
+        assert_eq!(
+            select(Language::C, "struct", json!({"name": "Point"}), c),
+            "/* a point */\nstruct Point {\n  int x;\n};"
+        );
+
+        let python = "def first():\n    pass\n\n# greeting\n@cache\ndef hello():\n    pass\n";
+        assert_eq!(
+            select(Language::Python, "fn", json!({"name": "hello"}), python),
+            "# greeting\n@cache\ndef hello():\n    pass"
+        );
+        assert_eq!(
+            select(Language::Python, "fn", json!({"name": "first"}), python),
+            "def first():\n    pass"
+        );
+
+        let go = "package main\n\n// Area of it\nfunc (s Square) Area() int {\n\treturn 1\n}\n";
+        assert_eq!(
+            select(Language::Go, "method", json!({"name": "Area"}), go),
+            "// Area of it\nfunc (s Square) Area() int {\n\treturn 1\n}"
+        );
+
+        let ts = "/** a shape */\nexport interface Shape {\n  area(): number;\n}\n";
+        assert_eq!(
+            select(
+                Language::Typescript,
+                "interface",
+                json!({"name": "Shape"}),
+                ts
+            ),
+            ts.trim_end()
+        );
+
+        let html = "<body>\n  <nav id=\"menu\">\n    <a>home</a>\n  </nav>\n</body>\n";
+        assert_eq!(
+            select(Language::Html, "id", json!({"id": "menu"}), html),
+            "  <nav id=\"menu\">\n    <a>home</a>\n  </nav>"
+        );
+
+        let css = "body {\n  margin: 0;\n}\n\n.card {\n  padding: 1em;\n}\n";
+        assert_eq!(
+            select(Language::Css, "rule", json!({"name": ".card"}), css),
+            ".card {\n  padding: 1em;\n}"
+        );
+
+        let sql = "CREATE TABLE users (id INT);\n\nCREATE TABLE posts (\n  id INT\n);\n";
+        assert_eq!(
+            select(Language::Sql, "table", json!({"name": "posts"}), sql),
+            "CREATE TABLE posts (\n  id INT\n);"
+        );
+    }
+}
@@ -0,0 +1,225 @@
+//! Query Variables
+//!
+//! Finds which bindings a query template refers to so anchors
+//! can be checked against them before the query is rendered.
+
+use handlebars::template::{HelperTemplate, Parameter, Template, TemplateElement};
+use handlebars::{JsonValue as Value, Path, PathSeg};
+use std::collections::BTreeSet;
+
+/// Bindings referred to by a query template
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Variables {
+    /// used outside of any `{{#if}}` or `{{#unless}}`
+    pub required: BTreeSet<String>,
+
+    /// only used within an `{{#if}}` or `{{#unless}}`
+    pub optional: BTreeSet<String>,
+
+    /// iterated over with `{{#each}}`, which
+    /// is given a list of every value bound
+    pub lists: BTreeSet<String>,
+}
+
+impl Variables {
+    pub fn from_template(template: &Template) -> Self {
+        let mut variables = Self::default();
+        variables.walk(&template.elements, Scope::default());
+        variables
+            .optional
+            .retain(|name| !variables.required.contains(name));
+        variables
+    }
+
+    /// whether `name` is used anywhere in the template
+    pub fn contains(&self, name: &str) -> bool {
+        self.required.contains(name) || self.optional.contains(name)
+    }
+
+    /// every variable filled with a stand in value, used
+    /// to render the template when checking the query
+    pub fn placeholders(&self) -> Value {
+        let placeholder = |name: &String| {
+            let value = Value::from("placeholder");
+            match self.lists.contains(name) {
+                true => (name.clone(), Value::Array(vec![value])),
+                false => (name.clone(), value),
+            }
+        };
+        let all = self.required.iter().chain(self.optional.iter());
+        Value::Object(all.map(placeholder).collect())
+    }
+
+    fn walk(&mut self, elements: &[TemplateElement], scope: Scope) {
+        for element in elements {
+            match element {
+                TemplateElement::Expression(helper) | TemplateElement::HtmlExpression(helper) => {
+                    if helper.params.is_empty() {
+                        self.add(&helper.name, scope);
+                    }
+                    self.add_params(helper, scope);
+                }
+                TemplateElement::HelperBlock(helper) => self.walk_block(helper, scope),
+                _ => (),
+            }
+        }
+    }
+
+    fn walk_block(&mut self, helper: &HelperTemplate, scope: Scope) {
+        let name = match &helper.name {
+            Parameter::Name(name) => name.as_str(),
+            _ => "",
+        };
+        match name {
+            // the block is scoped to each item
+            "each" => {
+                if let Some(name) = helper.params.first().and_then(|p| variable(p, scope)) {
+                    self.lists.insert(name.clone());
+                    self.insert(name, scope.optional);
+                }
+                self.walk_nested(helper, scope);
+            }
+            "with" => {
+                self.add_params(helper, scope);
+                self.walk_nested(helper, scope);
+            }
+            "if" | "unless" => {
+                let scope = Scope {
+                    optional: true,
+                    ..scope
+                };
+                self.add_params(helper, scope);
+                self.walk_templates(helper, scope);
+            }
+            _ => {
+                self.add_params(helper, scope);
+                self.walk_templates(helper, scope);
+            }
+        }
+    }
+
+    fn walk_templates(&mut self, helper: &HelperTemplate, scope: Scope) {
+        for template in helper.template.iter().chain(helper.inverse.iter()) {
+            self.walk(&template.elements, scope);
+        }
+    }
+
+    /// walks a block which moves into a new context, leaving
+    /// its `{{else}}` in the context it was found in
+    fn walk_nested(&mut self, helper: &HelperTemplate, scope: Scope) {
+        if let Some(template) = &helper.template {
+            let nested = Scope {
+                depth: scope.depth + 1,
+                ..scope
+            };
+            self.walk(&template.elements, nested);
+        }
+        if let Some(inverse) = &helper.inverse {
+            self.walk(&inverse.elements, scope);
+        }
+    }
+
+    fn add_params(&mut self, helper: &HelperTemplate, scope: Scope) {
+        for param in &helper.params {
+            self.add(param, scope);
+        }
+    }
+
+    fn add(&mut self, param: &Parameter, scope: Scope) {
+        if let Some(name) = variable(param, scope) {
+            self.insert(name, scope.optional);
+        }
+    }
+
+    fn insert(&mut self, name: String, optional: bool) {
+        match optional {
+            true => self.optional.insert(name),
+            false => self.required.insert(name),
+        };
+    }
+}
+
+/// Where an element sits within the template
+#[derive(Debug, Default, Clone, Copy)]
+struct Scope {
+    /// within an `{{#if}}` or `{{#unless}}`
+    optional: bool,
+
+    /// how many `{{#each}}` or `{{#with}}` blocks deep
+    depth: usize,
+}
+
+/// name of the top level binding a parameter refers to, if
+/// it refers to one rather than some field of the context
+/// an `{{#each}}` or `{{#with}}` moved into
+fn variable(param: &Parameter, scope: Scope) -> Option<String> {
+    let (segments, raw) = match param {
+        Parameter::Name(name) => return (scope.depth == 0).then(|| name.clone()),
+        Parameter::Path(Path::Relative((segments, raw))) => (segments, raw),
+        _ => return None,
+    };
+
+    // any leading segments are either `@root` or a `../`
+    let ups = segments
+        .iter()
+        .take_while(|segment| !matches!(segment, PathSeg::Named(_)))
+        .count();
+    if !raw.starts_with("@root") && ups < scope.depth {
+        return None;
+    }
+
+    match segments.get(ups)? {
+        PathSeg::Named(name) => Some(name.clone()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn variables(template: &str) -> Variables {
+        Variables::from_template(&Template::compile(template).unwrap())
+    }
+
+    #[test]
+    fn finds_required_optional_and_lists() {
+        let found = variables(
+            r#"(x @name (#eq? @name "{{name}}") {{#if kind}}(#eq? @kind "{{kind}}"){{/if}} (#any-of? @y {{#each names}}"{{this}}" {{/each}}))"#,
+        );
+        assert_eq!(
+            found.required,
+            BTreeSet::from(["name".into(), "names".into()])
+        );
+        assert_eq!(found.optional, BTreeSet::from(["kind".into()]));
+        assert_eq!(found.lists, BTreeSet::from(["names".into()]));
+        assert_eq!(
+            found.placeholders(),
+            serde_json::json!({"name": "placeholder", "kind": "placeholder", "names": ["placeholder"]})
+        );
+    }
+
+    #[test]
+    fn finds_variables_used_from_within_blocks() {
+        let found = variables(
+            r#"(x @y {{#each names}}(#eq? @y "{{this}}") (#eq? @z "{{../name}}") {{#if @root.kind}}"{{field}}"{{/if}}{{/each}} {{#with other}}"{{nested}}" "{{@root.kept}}"{{else}}"{{fallback}}"{{/with}})"#,
+        );
+        assert_eq!(
+            found.required,
+            BTreeSet::from([
+                "name".into(),
+                "names".into(),
+                "other".into(),
+                "kept".into(),
+                "fallback".into(),
+            ])
+        );
+        assert_eq!(found.optional, BTreeSet::from(["kind".into()]));
+        assert_eq!(found.lists, BTreeSet::from(["names".into()]));
+    }
+
+    #[test]
+    fn no_variables() {
+        assert_eq!(variables("(function_item) @match"), Variables::default());
+    }
+}
@@ -1,15 +1,39 @@
 use super::{File, Language};
-use tree_sitter::QueryMatch;
+use serde::{Deserialize, Serialize};
+use std::ops::RangeInclusive;
+use tree_sitter::{Node, QueryMatch};
 
-#[derive(Debug, Clone, Copy, Default)]
+// ranges are kept in the range cache, so any change to how they
+// are resolved or serialized must bump `cache::CACHE_FORMAT`
+
+/// Region of a [File] found by a query, tracked both
+/// as whole lines and as exact byte offsets
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct SourceRange {
+    /// first line of the range, starting from one
     pub start: usize,
+
+    /// last line of the range, inclusive
     pub end: usize,
+
+    /// byte offset into the source where the range begins
+    pub start_byte: usize,
+
+    /// byte offset into the source just past the range
+    pub end_byte: usize,
+
+    /// column, in bytes, on the first line where the range begins
+    pub start_column: usize,
+
+    /// column, in bytes, on the last line just past the range
+    pub end_column: usize,
 }
 
 impl SourceRange {
+    /// every line the range touches in full, even
+    /// if the range starts or stops part way in
     pub fn fetch_lines(&self, source: &File) -> String {
-        let Self { start, end } = self;
+        let Self { start, end, .. } = self;
         let mut string = String::new();
         let lines = source.contents.lines();
         for line in lines.skip(start - 1).take(end - start + 1) {
@@ -19,47 +43,178 @@ impl SourceRange {
         string.pop();
         string
     }
-}
 
-impl From<&QueryMatch<'_, '_>> for SourceRange {
-    fn from(value: &QueryMatch<'_, '_>) -> Self {
+    /// only the bytes covered by the range
+    pub fn fetch_bytes(&self, source: &File) -> String {
+        source
+            .contents
+            .get(self.start_byte..self.end_byte)
+            .unwrap_or_default()
+            .to_owned()
+    }
+
+    /// Range covering whole lines of the source, counting
+    /// from one, bytes ending before the last line break
+    pub fn from_lines(source: &str, lines: RangeInclusive<usize>) -> Self {
+        let (start, end) = lines.into_inner();
+        let mut range = Self {
+            start,
+            end,
+            start_byte: source.len(),
+            end_byte: source.len(),
+            ..Self::default()
+        };
+        let mut offset = 0;
+        for (row, line) in (1..).zip(source.split_inclusive('\n')) {
+            let text = line.strip_suffix('\n').unwrap_or(line);
+            let text = text.strip_suffix('\r').unwrap_or(text);
+            if row == start {
+                range.start_byte = offset;
+            }
+            if row == end {
+                range.end_byte = offset + text.len();
+                range.end_column = text.len();
+            }
+            offset += line.len();
+        }
+        range
+    }
+
+    /// Range covering every node captured by a match
+    /// of a query written for the given language
+    pub fn from_match(value: &QueryMatch<'_, '_>, language: &Language) -> Self {
+        Self::from_nodes(value.captures.iter().map(|capture| capture.node), language)
+    }
+
+    /// Range covering only the nodes captured with the
+    /// provided index, `None` if nothing was captured
+    pub fn from_capture(
+        value: &QueryMatch<'_, '_>,
+        index: u32,
+        language: &Language,
+    ) -> Option<Self> {
+        let mut nodes = value.nodes_for_capture_index(index).peekable();
+        nodes.peek()?;
+        Some(Self::from_nodes(nodes, language))
+    }
+
+    fn from_nodes<'a, I>(nodes: I, language: &Language) -> Self
+    where
+        I: IntoIterator<Item = Node<'a>>,
+    {
         let mut range = Self::default();
-        if value.captures.is_empty() {
+        let mut nodes = nodes.into_iter();
+        let Some(first) = nodes.next() else {
             return range;
+        };
+
+        // captures aren't always in order, so the range spans from
+        // the earliest start of any node to the latest end of any
+        let (mut first, mut last) = (first, first);
+        for node in nodes {
+            if node.start_byte() < first.start_byte() {
+                first = node;
+            }
+            if node.end_byte() > last.end_byte() {
+                last = node;
+            }
         }
 
-        let mut node = value.captures.first().unwrap().node;
-        range.start = node.start_position().row;
-        range.end = node.end_position().row;
+        range.start = first.start_position().row;
+        range.end = last.end_position().row;
+        range.start_byte = first.start_byte();
+        range.end_byte = last.end_byte();
+        range.start_column = first.start_position().column;
+        range.end_column = last.end_position().column;
 
-        value.captures[1..].iter().for_each(|capture| {
-            let row = capture.node.end_position().row;
-            if range.end < row {
-                node = capture.node;
-                range.end = row;
-            }
-        });
+        // block nodes, such as markdown sections or toml tables, can end at the
+        // start of the row after their last, which holds none of their bytes
+        if has_exclusive_end(language, &last) && range.end > range.start && range.end_column == 0 {
+            range.end -= 1;
+        }
 
-        // nodes for certain "block" types are reported by tree-sitter-md with exclusive ranges,
-        // ie the row in the end_position is one past the last row of the node.
-        if *node.language() == Language::Markdown.into() {
-            match node.kind() {
-                "section"
+        range.start += 1;
+        range.end += 1;
+        range
+    }
+}
+
+/// if the node is a kind of block which tree-sitter reports as
+/// ending at the start of the row following its last one
+fn has_exclusive_end(language: &Language, node: &Node) -> bool {
+    match language {
+        Language::Markdown => matches!(
+            node.kind(),
+            "section"
                 | "paragraph"
                 | "indented_code_block"
                 | "block_quote"
                 | "thematic_break"
                 | "list"
                 | "fenced_code_block"
-                | "html_block" => {
-                    range.end -= 1;
-                }
-                _ => (),
-            }
-        }
+                | "html_block"
+        ),
+        Language::Toml => matches!(node.kind(), "table" | "table_array_element"),
+        _ => false,
+    }
+}
 
-        range.start += 1;
-        range.end += 1;
-        range
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::source::Language;
+
+    #[test]
+    fn from_nodes_spans_every_node() {
+        let source = "fn a() {}\nfn b() {} fn c() {}\n";
+        let tree = Language::Rust.parse(source).unwrap().unwrap();
+        let root = tree.root_node();
+        let [a, b, c] = [0, 1, 2].map(|index| root.named_child(index).unwrap());
+
+        let range = SourceRange::from_nodes([b, a, c], &Language::Rust);
+        assert_eq!((range.start, range.end), (1, 2));
+        assert_eq!(range.start_byte, a.start_byte());
+        assert_eq!(range.end_byte, c.end_byte());
+        assert_eq!(range.end_column, c.end_position().column);
+
+        let range = SourceRange::from_nodes([c, b], &Language::Rust);
+        assert_eq!(
+            &source[range.start_byte..range.end_byte],
+            "fn b() {} fn c() {}"
+        );
+    }
+
+    #[test]
+    fn from_lines_covers_whole_lines() {
+        let source = "one\r\ntwo\nthree\n";
+        let range = SourceRange::from_lines(source, 2..=3);
+        assert_eq!(&source[range.start_byte..range.end_byte], "two\nthree");
+        assert_eq!(range.end_column, 5);
+
+        let range = SourceRange::from_lines(source, 1..=1);
+        assert_eq!(&source[range.start_byte..range.end_byte], "one");
+    }
+
+    #[test]
+    fn only_blocks_end_before_the_next_row() {
+        let source = "[a]\nb = 1\n[c]\nd = 2\n";
+        let tree = Language::Toml.parse(source).unwrap().unwrap();
+        let table = tree.root_node().named_child(0).unwrap();
+        assert_eq!(table.end_position(), tree_sitter::Point::new(2, 0));
+        let range = SourceRange::from_nodes([table], &Language::Toml);
+        assert_eq!((range.start, range.end), (1, 2));
+
+        let source = "# One\n\ntext\n";
+        let tree = Language::Markdown.parse(source).unwrap().unwrap();
+        let section = tree.root_node().named_child(0).unwrap();
+        let range = SourceRange::from_nodes([section], &Language::Markdown);
+        assert_eq!((range.start, range.end), (1, 3));
+
+        // other nodes keep every row they touch, even one they end
+        // at the very start of, just as they always have
+        let heading = section.named_child(0).unwrap();
+        assert_eq!(heading.end_position(), tree_sitter::Point::new(1, 0));
+        let range = SourceRange::from_nodes([heading], &Language::Markdown);
+        assert_eq!((range.start, range.end), (1, 2));
     }
 }
@@ -0,0 +1,7 @@
+// ANCHOR: outer
+struct Outer {
+    // ANCHOR: inner
+    inner: u8,
+    // ANCHOR_END: inner
+}
+// ANCHOR_END: outer
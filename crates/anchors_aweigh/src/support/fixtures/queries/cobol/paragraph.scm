@@ -0,0 +1 @@
+(paragraph) @match
@@ -0,0 +1,6 @@
+; a method and any comments directly above it
+(
+  (comment)*
+  .
+  (method name: (identifier) @name (#eq? @name "{{name}}"))
+) @match
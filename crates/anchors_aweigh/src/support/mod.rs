@@ -20,6 +20,33 @@ pub mod fixtures {
         read_to_string(sample_doc_filename()).unwrap()
     }
 
+    /// rust file with an `inner` named anchor nested in `outer`
+    pub fn anchored_filename() -> PathBuf {
+        fixtures_dir().join("anchored_file.rs")
+    }
+
+    /// includes `sample_doc.md`, which includes a ruby file
+    pub fn nested_doc_filename() -> PathBuf {
+        fixtures_dir().join("nested_doc.md")
+    }
+
+    /// links an anchor which selects nothing and
+    /// another with a template which doesn't exist
+    pub fn broken_include_filename() -> PathBuf {
+        fixtures_dir().join("broken_include.md")
+    }
+
+    /// includes `cycle_include.md` which includes it back
+    pub fn cycle_doc_filename() -> PathBuf {
+        fixtures_dir().join("cycle_doc.md")
+    }
+
+    /// query directory with `ruby/method.scm` and a
+    /// `cobol` directory for an unsupported language
+    pub fn queries_dirname() -> PathBuf {
+        fixtures_dir().join("queries")
+    }
+
     fn fixtures_dir() -> PathBuf {
         std::env::current_dir()
             .unwrap()
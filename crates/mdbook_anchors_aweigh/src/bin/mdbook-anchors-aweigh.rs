@@ -1,7 +1,10 @@
 use ::anyhow::Result;
 use ::clap::{Parser, Subcommand};
 use ::mdbook::preprocess::{CmdPreprocessor, Preprocessor};
-use ::mdbook_anchors_aweigh::AnchorsAweighLinker;
+use ::mdbook_anchors_aweigh::{
+    AnchorsAweighLinker, ChapterLogger, check_book, clean_book, watch_book,
+};
+use std::path::PathBuf;
 
 fn main() -> Result<()> {
     init_logging();
@@ -17,6 +20,13 @@ fn main() -> Result<()> {
             let book = linker.run(&ctx, book)?;
             serde_json::to_writer(std::io::stdout(), &book)?;
         }
+        Command::Check { book_dir } => {
+            if !check_book(&book_dir)? {
+                std::process::exit(1);
+            }
+        }
+        Command::Clean { book_dir } => clean_book(&book_dir)?,
+        Command::Watch { book_dir } => watch_book(&book_dir)?,
     }
     Ok(())
 }
@@ -41,6 +51,26 @@ enum Command {
     /// process `{{#aa ...}}` tags
     #[default]
     Process,
+    /// resolve every anchor in a book without rendering
+    /// it, exiting non-zero if any problems are found
+    Check {
+        /// directory containing `book.toml`
+        #[arg(default_value = ".")]
+        book_dir: PathBuf,
+    },
+    /// touch chapters whenever a file they link changes,
+    /// so that `mdbook serve` rebuilds them
+    Watch {
+        /// directory containing `book.toml`
+        #[arg(default_value = ".")]
+        book_dir: PathBuf,
+    },
+    /// remove the query cache kept in `cache_dir`
+    Clean {
+        /// directory containing `book.toml`
+        #[arg(default_value = ".")]
+        book_dir: PathBuf,
+    },
 }
 
 fn init_logging() {
@@ -50,7 +80,7 @@ fn init_logging() {
 
     let env = Env::default().filter_or("AA_LOG_LEVEL", "info");
 
-    Builder::from_env(env)
+    let logger = Builder::from_env(env)
         .format(|buf, record| {
             let header = match record.level() {
                 Level::Error => "⚓💥",
@@ -62,5 +92,11 @@ fn init_logging() {
             let timestamp = buf.timestamp();
             writeln!(buf, "{timestamp} {header}  {}", record.args())
         })
-        .init();
+        .build();
+
+    // chapters log from many threads, this puts it back in book order
+    let max_level = logger.filter();
+    log::set_boxed_logger(Box::new(ChapterLogger::new(logger)))
+        .expect("logging is only set up once");
+    log::set_max_level(max_level);
 }
@@ -0,0 +1,149 @@
+//! # Chapter Log
+//!
+//! Chapters are linked on many threads at once, so anything logged
+//! while linking one is held back and written out later in book
+//! order, keeping the log the same no matter which thread did the
+//! work.  This only happens when the [ChapterLogger] is installed.
+//!
+
+use ::log::{Level, Log, Metadata, Record};
+use std::cell::RefCell;
+
+thread_local! {
+    /// records held for the chapter being linked on this thread
+    static HELD: RefCell<Option<Vec<HeldRecord>>> = const { RefCell::new(None) };
+}
+
+/// Wraps the logger which does the writing, holding back
+/// records logged on a thread while it links a chapter
+#[derive(Debug)]
+pub struct ChapterLogger<L> {
+    inner: L,
+}
+
+impl<L> ChapterLogger<L> {
+    pub fn new(inner: L) -> Self {
+        Self { inner }
+    }
+}
+
+impl<L> Log for ChapterLogger<L>
+where
+    L: Log,
+{
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let held = HELD.with_borrow_mut(|held| {
+            held.as_mut()
+                .map(|held| held.push(HeldRecord::from(record)))
+                .is_some()
+        });
+        if !held {
+            self.inner.log(record);
+        }
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
+
+#[derive(Debug)]
+struct HeldRecord {
+    level: Level,
+    target: String,
+    message: String,
+}
+
+impl From<&Record<'_>> for HeldRecord {
+    fn from(record: &Record<'_>) -> Self {
+        Self {
+            level: record.level(),
+            target: record.target().to_owned(),
+            message: record.args().to_string(),
+        }
+    }
+}
+
+/// Records logged while linking a single chapter
+#[derive(Debug, Default)]
+pub(crate) struct HeldLog(Vec<HeldRecord>);
+
+impl HeldLog {
+    /// writes out every record in the order it was logged
+    pub fn replay(self) {
+        let logger = ::log::logger();
+        for record in self.0 {
+            logger.log(
+                &Record::builder()
+                    .level(record.level)
+                    .target(&record.target)
+                    .args(format_args!("{}", record.message))
+                    .build(),
+            );
+        }
+    }
+}
+
+/// Runs `link` holding back anything it logs on this thread
+pub(crate) fn hold<T, F>(link: F) -> (T, HeldLog)
+where
+    F: FnOnce() -> T,
+{
+    let outer = HELD.replace(Some(Vec::new()));
+    let value = link();
+    let held = HELD.replace(outer).unwrap_or_default();
+    (value, HeldLog(held))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct Written(Mutex<Vec<String>>);
+
+    impl Log for Written {
+        fn enabled(&self, _: &Metadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &Record) {
+            self.0.lock().unwrap().push(record.args().to_string());
+        }
+
+        fn flush(&self) {}
+    }
+
+    fn log(logger: &impl Log, message: &str) {
+        logger.log(
+            &Record::builder()
+                .level(Level::Warn)
+                .args(format_args!("{message}"))
+                .build(),
+        );
+    }
+
+    #[test]
+    fn records_are_held_while_linking() {
+        let logger = ChapterLogger::new(Written::default());
+
+        let ((), held) = hold(|| {
+            log(&logger, "one");
+            log(&logger, "two");
+        });
+        assert!(logger.inner.0.lock().unwrap().is_empty());
+        let messages: Vec<_> = held.0.iter().map(|held| held.message.as_str()).collect();
+        assert_eq!(messages, ["one", "two"]);
+
+        log(&logger, "three");
+        assert_eq!(*logger.inner.0.lock().unwrap(), ["three"]);
+    }
+}
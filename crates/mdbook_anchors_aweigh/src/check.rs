@@ -0,0 +1,77 @@
+use crate::preprocessor::link_anchors;
+use ::anyhow::Result;
+use ::mdbook::MDBook;
+use ::mdbook::book::BookItem;
+use std::io::Write;
+use std::path::Path;
+
+/// Resolves every anchor in the book found at `book_dir`
+/// without rendering it, printing a report for each chapter.
+/// Returns `true` when no problems were found.
+pub fn check_book(book_dir: &Path) -> Result<bool> {
+    report_book(book_dir, &mut std::io::stdout().lock())
+}
+
+/// same as [check_book] with the report written to `out`
+fn report_book<W>(book_dir: &Path, out: &mut W) -> Result<bool>
+where
+    W: Write,
+{
+    let book = MDBook::load(book_dir)?;
+    let linker = crate::build_book_linker(&book.root, &book.config)?;
+    let src_dir = book.root.join(&book.config.book.src);
+    let mut chapters = 0;
+    let mut failed = 0;
+
+    for item in book.iter() {
+        let BookItem::Chapter(chapter) = item else {
+            continue;
+        };
+        let Some(path) = &chapter.source_path else {
+            continue;
+        };
+        chapters += 1;
+
+        let problems = match link_anchors(&src_dir, Some(path), &chapter.content, &linker) {
+            Ok(linked) if linked.problems.is_empty() => {
+                writeln!(out, "✅ {} ({} anchors)", path.display(), linked.anchors)?;
+                continue;
+            }
+            Ok(linked) => linked.problems,
+            Err(error) => vec![format!("{error:#}")],
+        };
+
+        failed += 1;
+        writeln!(out, "💥 {}", path.display())?;
+        for problem in problems {
+            for line in problem.lines() {
+                writeln!(out, "     {line}")?;
+            }
+        }
+    }
+
+    writeln!(out, "\n{chapters} chapters checked, {failed} with problems")?;
+    Ok(failed == 0)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::support::fixtures;
+
+    #[test]
+    fn problems_are_reported_by_chapter() {
+        let mut out = Vec::new();
+        let passed = report_book(&fixtures::book_dirname(), &mut out).unwrap();
+        let report = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = report.lines().collect();
+
+        assert!(!passed);
+        assert_eq!(lines[0], "✅ linked.md (1 anchors)");
+        assert_eq!(lines[1], "💥 missing.md");
+        assert!(lines[2].contains("missing.rb"));
+        assert!(lines.contains(&"💥 nothing.md"));
+        assert!(report.contains("sample.rb#class?name=Nope}} selected nothing"));
+        assert!(report.ends_with("\n3 chapters checked, 2 with problems\n"));
+    }
+}
@@ -0,0 +1,22 @@
+use ::anchors_aweigh::source::RangeCache;
+use ::anyhow::{Context, Result};
+use ::mdbook::Config as BookConfig;
+use std::path::Path;
+
+/// Removes the `cache_dir` configured for the book
+/// found at `book_dir` along with everything in it
+pub fn clean_book(book_dir: &Path) -> Result<()> {
+    let book_config = BookConfig::from_disk(book_dir.join("book.toml"))?;
+    let config = crate::Config::try_from(&book_config)?;
+
+    let Some(dir) = crate::cache_dir(book_dir, &config) else {
+        println!("no cache_dir configured, nothing to clean");
+        return Ok(());
+    };
+
+    RangeCache::new(&dir)
+        .clear()
+        .with_context(|| format!("removing {dir:?}"))?;
+    println!("🧹 removed {}", dir.display());
+    Ok(())
+}
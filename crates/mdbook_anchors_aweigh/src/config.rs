@@ -1,4 +1,5 @@
 use ::anyhow::{Context, Result};
+use ::mdbook::Config as BookConfig;
 use ::mdbook::preprocess::PreprocessorContext;
 use toml::{Value, map::Map};
 
@@ -15,6 +16,55 @@ impl Config<'_> {
             .unwrap_or_default()
     }
 
+    pub fn string(&self, key: &str) -> Option<String> {
+        match self.toml.get(key)? {
+            Value::String(value) => Some(value.clone()),
+            _ => {
+                ::log::error!("[preprocessor.anchors-aweigh.{}] is not a string", key);
+                None
+            }
+        }
+    }
+
+    pub fn bool(&self, key: &str) -> Option<bool> {
+        match self.toml.get(key)? {
+            Value::Boolean(value) => Some(*value),
+            _ => {
+                ::log::error!("[preprocessor.anchors-aweigh.{}] is not a boolean", key);
+                None
+            }
+        }
+    }
+
+    pub fn integer(&self, key: &str) -> Option<u64> {
+        match self.toml.get(key)? {
+            Value::Integer(value) if *value >= 0 => Some(*value as u64),
+            _ => {
+                ::log::error!(
+                    "[preprocessor.anchors-aweigh.{}] is not a positive integer",
+                    key
+                );
+                None
+            }
+        }
+    }
+
+    pub fn strings(&self, key: &str) -> Option<Vec<String>> {
+        let strings = self.toml.get(key)?.as_array().and_then(|values| {
+            values
+                .iter()
+                .map(|value| value.as_str().map(String::from))
+                .collect::<Option<Vec<_>>>()
+        });
+        if strings.is_none() {
+            ::log::error!(
+                "[preprocessor.anchors-aweigh.{}] is not a list of strings",
+                key
+            );
+        }
+        strings
+    }
+
     pub fn try_each_table<F>(&self, key: &str, mut update: F)
     where
         F: FnMut(&str, &Map<String, Value>) -> Result<()>,
@@ -66,8 +116,15 @@ impl<'a> TryFrom<&'a PreprocessorContext> for Config<'a> {
     type Error = anyhow::Error;
 
     fn try_from(ctx: &'a PreprocessorContext) -> Result<Self> {
-        let toml = ctx
-            .config
+        Config::try_from(&ctx.config)
+    }
+}
+
+impl<'a> TryFrom<&'a BookConfig> for Config<'a> {
+    type Error = anyhow::Error;
+
+    fn try_from(config: &'a BookConfig) -> Result<Self> {
+        let toml = config
             .get_preprocessor("anchors-aweigh")
             .context("[preprocessor.anchors-aweigh] config missing")?;
         Ok(Config { toml })
@@ -1,30 +1,78 @@
-use ::anchors_aweigh::{linker::Linker, source::Language};
+use ::anchors_aweigh::doc::{DocList, ParseOptions};
+use ::anchors_aweigh::linker::{BodyElision, Linker};
+use ::anchors_aweigh::source::{Grammar, LanguageMap, QueryList, RangeCache, SourceList};
 use ::anyhow::{Result, bail};
+use ::mdbook::Config as BookConfig;
 use ::mdbook::preprocess::PreprocessorContext;
+use std::path::{Path, PathBuf};
+use toml::{Value, map::Map};
 
+mod chapter_log;
+mod check;
+mod clean;
 mod config;
+mod manifest;
 mod preprocessor;
+mod watch;
 
+#[cfg(test)]
+mod support;
+
+pub use chapter_log::ChapterLogger;
+pub use check::check_book;
+pub use clean::clean_book;
 pub use config::Config;
 pub use preprocessor::AnchorsAweighLinker;
+pub use watch::watch_book;
 
 pub fn build_linker(ctx: &PreprocessorContext) -> Result<Linker> {
+    build_book_linker(&ctx.root, &ctx.config)
+}
+
+/// Builds the linker for the book found at `root`
+/// which is configured by `book_config`
+pub fn build_book_linker(root: &Path, book_config: &BookConfig) -> Result<Linker> {
     ::log::debug!("building linker");
     let mut linker = Linker::default();
-    let config = Config::try_from(ctx)?;
+    let config = Config::try_from(book_config)?;
+
+    // compiled grammars are kept with the book, never anywhere
+    // shared where someone else could swap in their own library
+    let grammar_dir = cache_dir(root, &config)
+        .unwrap_or_else(|| root.join(".anchors-cache"))
+        .join("grammars");
+    let mut languages = LanguageMap::default();
+    config.try_each_table("grammars", |name, grammar| {
+        languages.register(load_grammar(root, &grammar_dir, name, grammar)?)?;
+        ::log::trace!("loaded grammar [{name}]");
+        Ok(())
+    });
+
+    config.try_each_string("languages", |pattern, name| {
+        let Some(language) = languages.named(name) else {
+            bail!("[{name}] is not supported.")
+        };
+        languages.insert(pattern, language)?;
+        Ok(())
+    });
+    linker.sources = SourceList::default()
+        .with_languages(languages)
+        .with_revision(config.string("source_rev"));
+
+    if config.bool("standard_queries") != Some(false) {
+        linker.queries = QueryList::standard();
+    }
+
+    if let Some(query_dir) = config.string("query_dir") {
+        let languages = linker.sources.languages();
+        for error in linker.queries.load_dir(root.join(query_dir), languages) {
+            ::log::error!("[preprocessor.anchors-aweigh.query_dir] [{error}]");
+        }
+    }
 
     config.try_each_table("queries", |lang, queries| {
-        let language = match lang {
-            "ruby" => Language::Ruby,
-            "rust" => Language::Rust,
-            "toml" => Language::Toml,
-            "javascript" => Language::Javascript,
-            "elixir" => Language::Elixir,
-            "json" => Language::Json,
-            "markdown" => Language::Markdown,
-            not_supported => {
-                bail!("[{not_supported}] is not supported.")
-            }
+        let Some(language) = linker.sources.languages().named(lang) else {
+            bail!("[{lang}] is not supported.")
         };
 
         for (name, query_tpl) in queries {
@@ -50,6 +98,80 @@ pub fn build_linker(ctx: &PreprocessorContext) -> Result<Linker> {
         Ok(())
     });
 
+    if let Some(separator) = config.string("match_separator") {
+        linker.settings.match_separator = separator;
+    }
+
+    let mut parse_options = ParseOptions::default();
+
+    if let Some(verbatim) = config.bool("verbatim_code_spans") {
+        parse_options.verbatim_code_spans = verbatim;
+    }
+
+    if let Some(verbatim) = config.bool("verbatim_fences") {
+        parse_options.verbatim_fences = verbatim;
+    }
+
+    if let Some(includes) = config.bool("mdbook_includes") {
+        parse_options.mdbook_includes = includes;
+    }
+
+    linker.docs = DocList::new(root)?.with_options(parse_options);
+
+    if let Some(extensions) = config.strings("expand_extensions") {
+        linker.settings.expand_extensions = extensions;
+    }
+
+    if let Some(depth) = config.integer("max_include_depth") {
+        linker.settings.max_include_depth = depth as usize;
+    }
+
+    config.try_each_string("hidden_prefixes", |name, prefix| {
+        let Some(language) = linker.sources.languages().named(name) else {
+            bail!("[{name}] is not supported.")
+        };
+        linker
+            .settings
+            .hidden_prefixes
+            .insert(language, prefix.to_owned());
+        Ok(())
+    });
+
+    config.try_each_table("elide_bodies", |name, table| {
+        let Some(language) = linker.sources.languages().named(name) else {
+            bail!("[{name}] is not supported.")
+        };
+        let elision = linker
+            .settings
+            .body_elisions
+            .entry(language)
+            .or_insert_with(|| BodyElision::new(Vec::<String>::new(), "..."));
+        if let Some(kinds) = table.get("kinds") {
+            let Some(kinds) = kinds.as_array().and_then(|values| {
+                values
+                    .iter()
+                    .map(|value| value.as_str().map(String::from))
+                    .collect::<Option<Vec<_>>>()
+            }) else {
+                bail!("`kinds` is not a list of strings")
+            };
+            elision.kinds = kinds;
+        }
+        if let Some(placeholder) = table.get("placeholder") {
+            let Some(placeholder) = placeholder.as_str() else {
+                bail!("`placeholder` is not a string")
+            };
+            elision.placeholder = placeholder.to_owned();
+        }
+        Ok(())
+    });
+
+    if let Some(keep) = config.bool("keep_anchor_markers") {
+        linker.settings.keep_anchor_markers = keep;
+    }
+
+    linker.cache = cache_dir(root, &config).map(RangeCache::new);
+
     config.try_each_string("templates", |name, template| {
         linker.templates.create(name, template)?;
         ::log::trace!("registered template [{name}]");
@@ -59,3 +181,43 @@ pub fn build_linker(ctx: &PreprocessorContext) -> Result<Linker> {
     ::log::debug!("linker built");
     Ok(linker)
 }
+
+/// where query ranges are cached for the book at `root`, if anywhere
+pub(crate) fn cache_dir(root: &Path, config: &Config) -> Option<PathBuf> {
+    config.string("cache_dir").map(|dir| root.join(dir))
+}
+
+/// Loads a grammar from its `[preprocessor.anchors-aweigh.grammars.<name>]`
+/// table, either a compiled `library` or a `source` directory to compile
+fn load_grammar(
+    root: &Path,
+    grammar_dir: &Path,
+    name: &str,
+    table: &Map<String, Value>,
+) -> Result<Grammar> {
+    let extensions: Vec<String> = table
+        .get("extensions")
+        .and_then(Value::as_array)
+        .map(|values| {
+            values
+                .iter()
+                .filter_map(Value::as_str)
+                .map(String::from)
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let library = table.get("library").and_then(Value::as_str);
+    let source = table.get("source").and_then(Value::as_str);
+    let symbol = table.get("symbol").and_then(Value::as_str);
+
+    Ok(match (library, source) {
+        (Some(library), None) => {
+            Grammar::load_library(name, root.join(library), symbol, extensions)?
+        }
+        (None, Some(source)) => {
+            Grammar::compile_source(name, root.join(source), grammar_dir, extensions)?
+        }
+        _ => bail!("expected either `library` or `source` to be set"),
+    })
+}
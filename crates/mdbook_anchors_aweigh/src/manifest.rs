@@ -0,0 +1,143 @@
+use ::anyhow::{Context, Result};
+use ::serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+/// The source files linked by the anchors of each chapter, which
+/// is everything a chapter needs to be rebuilt after changing
+#[derive(Debug, Default, Serialize)]
+pub struct Manifest {
+    chapters: BTreeMap<PathBuf, BTreeSet<PathBuf>>,
+}
+
+impl Manifest {
+    pub fn insert(&mut self, chapter: PathBuf, dependencies: BTreeSet<PathBuf>) {
+        self.chapters.insert(chapter, dependencies);
+    }
+
+    /// every chapter in the manifest
+    pub fn chapters(&self) -> impl Iterator<Item = &Path> {
+        self.chapters.keys().map(PathBuf::as_path)
+    }
+
+    /// every file some chapter depends on
+    pub fn dependencies(&self) -> BTreeSet<&Path> {
+        self.chapters
+            .values()
+            .flatten()
+            .map(PathBuf::as_path)
+            .collect()
+    }
+
+    /// chapters which depend on any of the `changed` files
+    pub fn affected<'a>(&'a self, changed: &'a BTreeSet<&Path>) -> impl Iterator<Item = &'a Path> {
+        self.chapters
+            .iter()
+            .filter(|(_, dependencies)| {
+                dependencies
+                    .iter()
+                    .any(|path| changed.contains(path.as_path()))
+            })
+            .map(|(chapter, _)| chapter.as_path())
+    }
+
+    /// Writes the manifest to `path` as a Makefile style
+    /// rule per chapter when it ends in `.d`, otherwise as JSON
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let data = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("d") => self.to_makefile(),
+            _ => serde_json::to_string_pretty(self)?,
+        };
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).with_context(|| format!("creating {dir:?}"))?;
+        }
+        std::fs::write(path, data).with_context(|| format!("writing {path:?}"))
+    }
+
+    fn to_makefile(&self) -> String {
+        let mut rules = String::new();
+        for (chapter, dependencies) in self.chapters.iter() {
+            let _ = write!(rules, "{}:", make_escape(chapter));
+            for path in dependencies {
+                let _ = write!(rules, " \\\n  {}", make_escape(path));
+            }
+            rules.push('\n');
+        }
+        rules
+    }
+}
+
+/// escapes the characters make treats specially in a rule
+fn make_escape(path: &Path) -> String {
+    let mut escaped = String::new();
+    for c in path.display().to_string().chars() {
+        match c {
+            ' ' | '#' => escaped.push('\\'),
+            '$' => escaped.push('$'),
+            _ => {}
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn manifest() -> Manifest {
+        let mut manifest = Manifest::default();
+        manifest.insert(
+            PathBuf::from("src/intro.md"),
+            BTreeSet::from([PathBuf::from("lib.rs"), PathBuf::from("my file#1.rs")]),
+        );
+        manifest.insert(
+            PathBuf::from("src/usage.md"),
+            BTreeSet::from([PathBuf::from("lib.rs")]),
+        );
+        manifest
+    }
+
+    #[test]
+    fn writes_makefile_rules_and_json() {
+        let dir = std::env::temp_dir().join(format!("aa-manifest-{}", std::process::id()));
+        let manifest = manifest();
+
+        manifest.write(&dir.join("deps.d")).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dir.join("deps.d")).unwrap(),
+            "src/intro.md: \\\n  lib.rs \\\n  my\\ file\\#1.rs\nsrc/usage.md: \\\n  lib.rs\n"
+        );
+
+        manifest.write(&dir.join("deps.json")).unwrap();
+        let json: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(dir.join("deps.json")).unwrap()).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "chapters": {
+                    "src/intro.md": ["lib.rs", "my file#1.rs"],
+                    "src/usage.md": ["lib.rs"],
+                }
+            })
+        );
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn changed_files_affect_the_chapters_linking_them() {
+        let manifest = manifest();
+
+        let changed = BTreeSet::from([Path::new("my file#1.rs")]);
+        let affected: Vec<_> = manifest.affected(&changed).collect();
+        assert_eq!(affected, [Path::new("src/intro.md")]);
+
+        let changed = BTreeSet::from([Path::new("lib.rs")]);
+        assert_eq!(manifest.affected(&changed).count(), 2);
+
+        let changed = BTreeSet::from([Path::new("other.rs")]);
+        assert_eq!(manifest.affected(&changed).count(), 0);
+    }
+}
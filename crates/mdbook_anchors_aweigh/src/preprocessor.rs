@@ -1,7 +1,14 @@
-use ::anchors_aweigh::{doc::DocFile, linker::Linker};
-use ::anyhow::{Context, Result};
-use ::mdbook::book::{Book, BookItem, Chapter};
+use crate::chapter_log::{self, HeldLog};
+use crate::manifest::Manifest;
+use ::anchors_aweigh::doc::DocFile;
+use ::anchors_aweigh::linker::{Linker, Node};
+use ::anyhow::{Context, Result, bail};
+use ::mdbook::book::{Book, BookItem};
 use ::mdbook::preprocess::{Preprocessor, PreprocessorContext};
+use std::collections::BTreeSet;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 #[derive(Debug)]
 pub struct AnchorsAweighLinker;
@@ -15,36 +22,241 @@ impl Preprocessor for AnchorsAweighLinker {
         ::log::info!("{} Starting", self.name());
 
         let linker = crate::build_linker(ctx)?;
+        let config = crate::Config::try_from(ctx)?;
+        let strict = config.bool("strict").unwrap_or(false);
+        let threads = match config.integer("threads") {
+            Some(threads @ 1..) => threads as usize,
+            _ => std::thread::available_parallelism().map_or(1, NonZeroUsize::get),
+        };
+        let src_dir = ctx.root.join(&ctx.config.book.src);
+
+        let mut chapters = Vec::new();
         book.for_each_mut(|item| {
             if let BookItem::Chapter(chapter) = item {
-                match link_anchors(ctx, chapter, &linker) {
-                    Ok(updated) => {
-                        chapter.content = updated;
+                chapters.push((chapter.source_path.clone(), chapter.content.clone()));
+            }
+        });
+
+        // chapters are linked in any order however results are
+        // applied and logged in book order, along with anything
+        // logged while linking, so output is the same no matter
+        // how many threads did the work
+        let mut results = link_chapters(&src_dir, &chapters, &linker, threads).into_iter();
+        let mut problems = Vec::new();
+        let mut manifest = Manifest::default();
+        let mut failed = false;
+
+        book.for_each_mut(|item| {
+            if let BookItem::Chapter(chapter) = item {
+                let (result, log) = results.next().expect("a result for every chapter");
+                log.replay();
+                match result {
+                    Ok(linked) => {
+                        chapter.content = linked.content;
+                        if let Some(path) = &chapter.source_path {
+                            manifest.insert(src_dir.join(path), linked.dependencies);
+                        }
+                        for problem in linked.problems {
+                            ::log::warn!("{problem}");
+                            problems.push(problem);
+                        }
                     }
                     Err(error) => {
+                        failed = true;
                         log::error!("{} {:?}", error, error.source());
+                        problems.push(format!("{error:#}"));
                     }
                 }
             }
         });
 
+        // every chapter read what it needed from the cache, so anything
+        // else was left by older sources; a chapter which failed may
+        // not have, so nothing is pruned until all of them link
+        if let Some(cache) = &linker.cache
+            && !failed
+        {
+            match cache.prune() {
+                Ok(removed) => ::log::debug!("pruned {removed} stale cache entries"),
+                Err(error) => ::log::warn!("could not prune {:?}: {error}", cache.dir()),
+            }
+        }
+
+        if let Some(path) = config.string("dependency_file")
+            && let Err(error) = manifest.write(&ctx.root.join(path))
+        {
+            ::log::error!("[preprocessor.anchors-aweigh.dependency_file] [{error:#}]");
+        }
+
+        if strict && !problems.is_empty() {
+            bail!(
+                "{} problem(s) found while linking anchors:\n  {}",
+                problems.len(),
+                problems.join("\n  ")
+            );
+        }
+
         ::log::info!("{} Finished", self.name());
         Ok(book)
     }
 }
 
-fn link_anchors(
-    ctx: &PreprocessorContext,
-    chapter: &mut Chapter,
+/// chapter contents after linking along with every
+/// problem found which did not stop it from linking
+pub(crate) struct Linked {
+    pub content: String,
+    pub problems: Vec<String>,
+    pub anchors: usize,
+    /// every source file the anchors linked
+    pub dependencies: BTreeSet<PathBuf>,
+}
+
+/// Links each `(source_path, content)` chapter spread across
+/// `threads` workers, results and whatever was logged while
+/// linking being in the same order given
+fn link_chapters(
+    src_dir: &Path,
+    chapters: &[(Option<PathBuf>, String)],
     linker: &Linker,
-) -> Result<String> {
-    let path = chapter.source_path.clone().context("missing source path")?;
-    let path = ctx.config.book.src.join(path);
+    threads: usize,
+) -> Vec<(Result<Linked>, HeldLog)> {
+    let link = |(path, content): &(Option<PathBuf>, String)| {
+        chapter_log::hold(|| link_anchors(src_dir, path.as_deref(), content, linker))
+    };
 
-    let docfile = DocFile::with_path_and_source(path.clone(), &chapter.content)?;
+    let threads = threads.min(chapters.len());
+    if threads <= 1 {
+        return chapters.iter().map(link).collect();
+    }
+
+    let next = AtomicUsize::new(0);
+    let mut results: Vec<(usize, (Result<Linked>, HeldLog))> = std::thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut linked = Vec::new();
+                    loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(chapter) = chapters.get(index) else {
+                            break linked;
+                        };
+                        linked.push((index, link(chapter)));
+                    }
+                })
+            })
+            .collect();
+
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().expect("chapter worker panicked"))
+            .collect()
+    });
+
+    results.sort_by_key(|(index, _)| *index);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+pub(crate) fn link_anchors(
+    src_dir: &Path,
+    source_path: Option<&Path>,
+    content: &str,
+    linker: &Linker,
+) -> Result<Linked> {
+    // draft chapters have no file and nothing to link
+    let Some(path) = source_path else {
+        return Ok(Linked {
+            content: content.to_owned(),
+            problems: Vec::new(),
+            anchors: 0,
+            dependencies: BTreeSet::new(),
+        });
+    };
+    let path = src_dir.join(path);
+
+    let docfile = DocFile::with_options(path.clone(), content, linker.docs.options())
+        .with_context(|| format!("parsing {path:?}"))?;
 
     let assembly = linker
         .build_assembly(&docfile)
         .with_context(|| format!("linking {path:?}"))?;
-    Ok(assembly.compile(linker))
+
+    let anchors = assembly
+        .nodes
+        .iter()
+        .filter(|node| matches!(node, Node::Link(_)))
+        .count();
+
+    let mut problems: Vec<String> = assembly
+        .unresolved()
+        .map(|link| {
+            let revision = match &link.source.revision {
+                Some(revision) => format!("@{revision}"),
+                None => String::new(),
+            };
+            format!(
+                "{path:?}: {{{{#aa {}{revision}{}}}}} selected nothing",
+                link.source.path.display(),
+                link.strategy
+            )
+        })
+        .collect();
+
+    let (content, errors) = assembly.compile_with_errors(linker);
+    problems.extend(
+        assembly.nested_errors().map(|(include, error)| {
+            format!("{path:?}: [{}] in {include:?}: {error}", error.kind())
+        }),
+    );
+    problems.extend(
+        errors
+            .iter()
+            .map(|error| format!("{path:?}: [{}] {error}", error.kind())),
+    );
+
+    Ok(Linked {
+        content,
+        problems,
+        anchors,
+        dependencies: assembly.dependencies(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn draft_chapters_are_left_alone() {
+        let linker = Linker::default();
+        let linked = link_anchors(Path::new("src"), None, "", &linker).unwrap();
+        assert!(linked.content.is_empty());
+        assert!(linked.problems.is_empty());
+        assert_eq!(linked.anchors, 0);
+    }
+
+    #[test]
+    fn problems_are_labelled_by_kind() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("aa-problem-kinds-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        std::fs::write(dir.join("lines.txt"), "one\n")?;
+        std::fs::write(dir.join("included.md"), "{{#aa (missing) lines.txt}}\n")?;
+
+        let linker = Linker::default();
+        let content = "{{#aa included.md}}\n{{#aa (gone) lines.txt}}\n";
+        let linked = link_anchors(&dir, Some(Path::new("chapter.md")), content, &linker)?;
+        std::fs::remove_dir_all(&dir)?;
+
+        let chapter = dir.join("chapter.md");
+        let included = dir.join("included.md");
+        assert_eq!(
+            linked.problems,
+            [
+                format!(
+                    "{chapter:?}: [TemplateMissing] in {included:?}: Template not found missing"
+                ),
+                format!("{chapter:?}: [TemplateMissing] Template not found gone"),
+            ]
+        );
+        Ok(())
+    }
 }
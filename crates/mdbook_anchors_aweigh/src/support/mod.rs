@@ -0,0 +1,15 @@
+pub mod fixtures {
+    use std::path::PathBuf;
+
+    /// book with a chapter linking `sample.rb`, one linking a file
+    /// which doesn't exist and one with a query matching nothing
+    pub fn book_dirname() -> PathBuf {
+        fixtures_dir().join("book")
+    }
+
+    fn fixtures_dir() -> PathBuf {
+        std::env::current_dir()
+            .unwrap()
+            .join("src/support/fixtures")
+    }
+}
@@ -0,0 +1,143 @@
+use crate::manifest::Manifest;
+use crate::preprocessor::link_anchors;
+use ::anyhow::Result;
+use ::mdbook::MDBook;
+use ::mdbook::book::BookItem;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// how long to wait between looking for changed files
+const POLL: Duration = Duration::from_millis(500);
+
+/// Watches every source file linked by the book found at `book_dir`,
+/// touching the chapters which link a file whenever it changes.
+/// `mdbook serve` and `mdbook watch` only watch the book itself, so
+/// touching a chapter is what gets them to rebuild it.
+pub fn watch_book(book_dir: &Path) -> Result<()> {
+    let mut manifest = scan(book_dir)?;
+    let mut seen = modified(&manifest);
+    println!(
+        "👀 watching {} files linked from {} chapters",
+        manifest.dependencies().len(),
+        manifest.chapters().count()
+    );
+
+    loop {
+        std::thread::sleep(POLL);
+        let now = modified(&manifest);
+        let changed: BTreeSet<&Path> = now
+            .iter()
+            .filter(|(path, time)| seen.get(*path) != Some(time))
+            .map(|(path, _)| path.as_path())
+            .collect();
+        if changed.is_empty() {
+            continue;
+        }
+
+        for chapter in manifest.affected(&changed) {
+            println!("🔄 {}", chapter.display());
+            if let Err(error) = touch(chapter) {
+                ::log::error!("could not touch {chapter:?}: {error}");
+            }
+        }
+
+        // chapters may have been edited, linking other files
+        // now, so the book is scanned again for what to watch
+        manifest = match scan(book_dir) {
+            Ok(manifest) => manifest,
+            Err(error) => {
+                ::log::error!("{error:#}");
+                manifest
+            }
+        };
+        seen = modified(&manifest);
+    }
+}
+
+/// links every chapter of the book to find what each depends on
+fn scan(book_dir: &Path) -> Result<Manifest> {
+    let book = MDBook::load(book_dir)?;
+    let linker = crate::build_book_linker(&book.root, &book.config)?;
+    let src_dir = book.root.join(&book.config.book.src);
+    let mut manifest = Manifest::default();
+
+    for item in book.iter() {
+        let BookItem::Chapter(chapter) = item else {
+            continue;
+        };
+        let Some(path) = &chapter.source_path else {
+            continue;
+        };
+        let dependencies = match link_anchors(&src_dir, Some(path), &chapter.content, &linker) {
+            Ok(linked) => linked.dependencies,
+            Err(error) => {
+                ::log::warn!("{error:#}");
+                BTreeSet::new()
+            }
+        };
+        manifest.insert(src_dir.join(path), dependencies);
+    }
+    Ok(manifest)
+}
+
+/// when each chapter and dependency was last modified
+fn modified(manifest: &Manifest) -> BTreeMap<PathBuf, Option<SystemTime>> {
+    manifest
+        .chapters()
+        .chain(manifest.dependencies())
+        .map(|path| {
+            let time = std::fs::metadata(path).and_then(|meta| meta.modified());
+            (path.to_owned(), time.ok())
+        })
+        .collect()
+}
+
+fn touch(path: &Path) -> std::io::Result<()> {
+    std::fs::File::options()
+        .append(true)
+        .open(path)?
+        .set_modified(SystemTime::now())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::support::fixtures;
+
+    #[test]
+    fn scanned_sources_map_to_their_chapters() {
+        let book_dir = fixtures::book_dirname();
+        let src_dir = book_dir.join("src");
+        let manifest = scan(&book_dir).unwrap();
+
+        let sample = std::fs::canonicalize(src_dir.join("sample.rb")).unwrap();
+        assert_eq!(manifest.dependencies(), BTreeSet::from([sample.as_path()]));
+
+        let changed = BTreeSet::from([sample.as_path()]);
+        let affected: Vec<_> = manifest.affected(&changed).collect();
+        assert_eq!(
+            affected,
+            [src_dir.join("linked.md"), src_dir.join("nothing.md")]
+        );
+    }
+
+    #[test]
+    fn touching_updates_the_modified_time() {
+        let path = std::env::temp_dir().join(format!("aa-touch-{}.md", std::process::id()));
+        std::fs::write(&path, "# Touched\n").unwrap();
+        let past = SystemTime::now() - Duration::from_secs(60);
+        std::fs::File::options()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(past)
+            .unwrap();
+
+        touch(&path).unwrap();
+        let modified = std::fs::metadata(&path).unwrap().modified().unwrap();
+        assert!(modified > past);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "# Touched\n");
+        std::fs::remove_file(path).unwrap();
+    }
+}